@@ -0,0 +1,248 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use crate::module_graph::ModuleGraph;
+use crate::utils::short_hash;
+
+/// A file in the output directory that modules are emitted into
+pub struct Chunk<'a> {
+    pub filename: String,
+    /// The chunk's modules in dependency order
+    pub modules: Vec<&'a String>,
+    /// Modules that can be evaluated once the chunk is loaded, the entrypoint
+    /// or a module imported with `import()`. Empty for a shared chunk, which
+    /// is only loaded along with the chunks importing it.
+    pub entries: Vec<&'a String>,
+    /// Filenames of the shared chunks loaded along with this one
+    pub imports: Vec<String>,
+}
+
+/// Bundles every module reachable from `entry_id` into a single chunk
+pub fn create_chunk<'a>(
+    graph: &'a ModuleGraph,
+    entry_id: &'a String,
+    filename: String,
+) -> Chunk<'a> {
+    Chunk {
+        filename,
+        modules: graph.get_modules_in_order(entry_id),
+        entries: vec![entry_id],
+        imports: vec![],
+    }
+}
+
+/// Splits the modules reachable from `entry_id` into chunks, the first of
+/// which is the entry chunk of the modules loaded before the entry runs. Each
+/// module imported with `import()` that isn't in the entry chunk gets a chunk
+/// of the modules it loads, except modules loaded by several of them, which
+/// are moved into a shared chunk for each combination of those modules. No
+/// module is emitted twice, and loading a chunk only loads what it needs.
+pub fn split_chunks<'a>(
+    graph: &'a ModuleGraph,
+    entry_id: &'a String,
+    filename: String,
+) -> Vec<Chunk<'a>> {
+    let entry_modules = graph.get_sync_modules_in_order(entry_id);
+    let in_entry_chunk: HashSet<&String> = entry_modules.iter().cloned().collect();
+
+    // Modules imported with `import()`, including from the chunks of others,
+    // with the modules each of them loads that the entry chunk doesn't have
+    let mut async_entries: Vec<&String> = vec![];
+    let mut async_entry_modules: Vec<Vec<&String>> = vec![];
+    let mut modules_to_scan = entry_modules.clone();
+    loop {
+        for module_id in &modules_to_scan {
+            for edge in graph.get_dependencies(module_id) {
+                if edge.is_async()
+                    && !in_entry_chunk.contains(&edge.module_id)
+                    && !async_entries.contains(&&edge.module_id)
+                {
+                    async_entries.push(&edge.module_id);
+                }
+            }
+        }
+
+        let async_entry = match async_entries.get(async_entry_modules.len()) {
+            Some(async_entry) => *async_entry,
+            None => break,
+        };
+        modules_to_scan = graph
+            .get_sync_modules_in_order(async_entry)
+            .into_iter()
+            .filter(|module_id| !in_entry_chunk.contains(module_id))
+            .collect();
+        async_entry_modules.push(modules_to_scan.clone());
+    }
+
+    // Modules are grouped by the set of async entries loading them, in the
+    // order they're first loaded so each group stays in dependency order
+    let mut loaded_by: HashMap<&String, BTreeSet<usize>> = HashMap::new();
+    let mut module_order: Vec<&String> = vec![];
+    for (index, modules) in async_entry_modules.iter().enumerate() {
+        for module_id in modules {
+            loaded_by
+                .entry(module_id)
+                .or_insert_with(|| {
+                    module_order.push(module_id);
+                    BTreeSet::new()
+                })
+                .insert(index);
+        }
+    }
+
+    let mut groups: Vec<(&BTreeSet<usize>, Vec<&String>)> = vec![];
+    for module_id in module_order {
+        let group_key = &loaded_by[module_id];
+        match groups.iter_mut().find(|(key, _)| *key == group_key) {
+            Some((_, modules)) => modules.push(module_id),
+            None => groups.push((group_key, vec![module_id])),
+        }
+    }
+
+    let shared_groups: Vec<(&BTreeSet<usize>, String, Vec<&String>)> = groups
+        .iter()
+        .filter(|(key, _)| key.len() > 1)
+        .map(|(key, modules)| {
+            let loaded_by: Vec<&str> = key.iter().map(|index| &**async_entries[*index]).collect();
+            let filename = format!("shared.{}.js", short_hash(loaded_by.join("\n").as_bytes()));
+            (*key, filename, modules.clone())
+        })
+        .collect();
+
+    let mut chunks = vec![Chunk {
+        filename,
+        modules: entry_modules,
+        entries: vec![entry_id],
+        imports: vec![],
+    }];
+
+    for (index, async_entry) in async_entries.iter().enumerate() {
+        // Empty when every module it loads is shared with another async entry
+        let modules = groups
+            .iter()
+            .find(|(key, _)| key.len() == 1 && key.contains(&index))
+            .map_or(vec![], |(_, modules)| modules.clone());
+        let imports = shared_groups
+            .iter()
+            .filter(|(key, _, _)| key.contains(&index))
+            .map(|(_, filename, _)| filename.clone())
+            .collect();
+
+        chunks.push(Chunk {
+            filename: chunk_filename(async_entry),
+            modules,
+            entries: vec![async_entry],
+            imports,
+        });
+    }
+
+    for (_, filename, modules) in shared_groups {
+        chunks.push(Chunk {
+            filename,
+            modules,
+            entries: vec![],
+            imports: vec![],
+        });
+    }
+
+    chunks
+}
+
+/// Named after the module and a hash of its id, which is unique in the graph
+fn chunk_filename(module_id: &str) -> String {
+    let stem = Path::new(module_id.split('?').next().unwrap_or(module_id))
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("chunk");
+
+    format!("{}.{}.js", stem, short_hash(module_id.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js_module::{Dependency, ImportType};
+    use swc_common::DUMMY_SP;
+
+    /// Each edge is `(importer, module, is_async)`
+    fn create_graph(edges: &[(&str, &str, bool)]) -> ModuleGraph {
+        let mut graph = ModuleGraph::new();
+        for (id, dep_id, is_async) in edges {
+            let dependency = Dependency {
+                request: "./dep".into(),
+                import_type: ImportType::Require,
+                span: DUMMY_SP,
+                is_async: *is_async,
+                loc: None,
+            };
+            graph.add_dependency(id, dep_id, dependency);
+        }
+        graph
+    }
+
+    fn describe<'a>(chunks: &'a [Chunk]) -> Vec<(Vec<&'a str>, Vec<&'a str>, usize)> {
+        chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.entries.iter().map(|id| id.as_str()).collect(),
+                    chunk.modules.iter().map(|id| id.as_str()).collect(),
+                    chunk.imports.len(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moves_modules_loaded_by_several_async_imports_into_a_shared_chunk() {
+        let graph = create_graph(&[
+            ("index.js", "util.js", false),
+            ("index.js", "a.js", true),
+            ("index.js", "b.js", true),
+            ("a.js", "shared.js", false),
+            ("a.js", "util.js", false),
+            ("b.js", "shared.js", false),
+            ("shared.js", "lib.js", false),
+        ]);
+        let entry_id = String::from("index.js");
+        let chunks = split_chunks(&graph, &entry_id, String::from("main.js"));
+
+        assert_eq!(
+            describe(&chunks),
+            vec![
+                (vec!["index.js"], vec!["util.js", "index.js"], 0),
+                (vec!["a.js"], vec!["a.js"], 1),
+                (vec!["b.js"], vec!["b.js"], 1),
+                (vec![], vec!["lib.js", "shared.js"], 0),
+            ]
+        );
+        assert!(chunks[1].filename.starts_with("a."));
+        assert_eq!(chunks[1].imports, vec![chunks[3].filename.clone()]);
+        assert_eq!(chunks[2].imports, vec![chunks[3].filename.clone()]);
+    }
+
+    #[test]
+    fn splits_async_imports_of_async_chunks() {
+        let graph = create_graph(&[
+            ("index.js", "page.js", true),
+            ("index.js", "loaded.js", true),
+            ("index.js", "loaded.js", false),
+            ("page.js", "modal.js", true),
+            ("modal.js", "page.js", false),
+        ]);
+        let entry_id = String::from("index.js");
+        let chunks = split_chunks(&graph, &entry_id, String::from("main.js"));
+
+        // Modules in the entry chunk are never split, and `modal.js` shares
+        // `page.js` with the chunk importing it
+        assert_eq!(
+            describe(&chunks),
+            vec![
+                (vec!["index.js"], vec!["loaded.js", "index.js"], 0),
+                (vec!["page.js"], vec![], 1),
+                (vec!["modal.js"], vec!["modal.js"], 1),
+                (vec![], vec!["page.js"], 0),
+            ]
+        );
+    }
+}
@@ -1,7 +1,14 @@
+use crate::js_module::Location;
 use std::time::Duration;
 
 pub enum Diagnostic {
     ModuleBuildSuccess(ModuleBuildSuccess),
+    CircularDependency(CircularDependency),
+    NodeShim(NodeShim),
+    NodePolyfill(NodePolyfill),
+    MissingPolyfill(MissingPolyfill),
+    DynamicRequire(DynamicRequire),
+    InvalidGlob(InvalidGlob),
 }
 
 pub struct ModuleBuildSuccess {
@@ -9,6 +16,44 @@ pub struct ModuleBuildSuccess {
     pub duration: Duration,
 }
 
+pub struct CircularDependency {
+    pub cycle: Vec<String>,
+}
+
+/// A Node global declared for a module running in the browser
+pub struct NodeShim {
+    pub module_id: String,
+    pub name: String,
+}
+
+/// A Node built-in request resolved to a browser polyfill package
+pub struct NodePolyfill {
+    pub module_id: String,
+    pub request: String,
+    pub polyfill: String,
+}
+
+/// A Node built-in requested for the browser without a polyfill that resolves,
+/// so it's left external
+pub struct MissingPolyfill {
+    pub module_id: String,
+    pub request: String,
+}
+
+/// A require whose request is only known at runtime, so it isn't bundled
+pub struct DynamicRequire {
+    pub module_id: String,
+    pub loc: Option<Location>,
+}
+
+/// An `import.meta.glob` call that can't be expanded at build time, so it
+/// throws when it runs
+pub struct InvalidGlob {
+    pub module_id: String,
+    pub loc: Option<Location>,
+    pub message: String,
+}
+
 pub struct Diagnostics {
     diagnostics: Vec<Diagnostic>,
 }
@@ -24,6 +69,17 @@ impl Diagnostics {
         self.diagnostics.push(diagnostic);
     }
 
+    pub fn get_module_build_duration(&self, module_id: &str) -> Option<Duration> {
+        self.diagnostics
+            .iter()
+            .find_map(|diagnostic| match diagnostic {
+                Diagnostic::ModuleBuildSuccess(mds) if mds.module_id == module_id => {
+                    Some(mds.duration)
+                }
+                _ => None,
+            })
+    }
+
     pub fn print(&self) {
         for diagnostic in &self.diagnostics {
             match diagnostic {
@@ -34,7 +90,49 @@ impl Diagnostics {
                         &mds.duration.as_millis()
                     )
                 }
+                Diagnostic::NodeShim(shim) => {
+                    println!("Shimmed {} in {}", shim.name, shim.module_id)
+                }
+                Diagnostic::NodePolyfill(polyfill) => {
+                    println!(
+                        "Polyfilled {} with {} in {}",
+                        polyfill.request, polyfill.polyfill, polyfill.module_id
+                    )
+                }
+                Diagnostic::MissingPolyfill(missing) => {
+                    println!(
+                        "Warning: No polyfill found for {} in {}, so it isn't bundled",
+                        missing.request, missing.module_id
+                    )
+                }
+                Diagnostic::DynamicRequire(dynamic) => {
+                    println!(
+                        "Warning: Dynamic require in {} can't be bundled and throws when called",
+                        format_location(&dynamic.module_id, dynamic.loc)
+                    )
+                }
+                Diagnostic::InvalidGlob(invalid) => {
+                    println!(
+                        "Warning: {} in {}, so the glob throws when called",
+                        invalid.message,
+                        format_location(&invalid.module_id, invalid.loc)
+                    )
+                }
+                Diagnostic::CircularDependency(circular) => {
+                    println!(
+                        "Warning: Circular dependency {}",
+                        circular.cycle.join(" -> ")
+                    )
+                }
             }
         }
     }
 }
+
+/// Formats a location in a module as `module_id:line:column`
+fn format_location(module_id: &str, loc: Option<Location>) -> String {
+    match loc {
+        Some(loc) => format!("{}:{}:{}", module_id, loc.line, loc.column + 1),
+        None => module_id.to_string(),
+    }
+}
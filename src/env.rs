@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use crate::utils::to_js_string;
+use crate::Config;
+
+/// Loads `.env`, `.env.local`, `.env.[mode]` and `.env.[mode].local` from the
+/// project root, with later files and then the process environment taking
+/// precedence.
+pub fn load_env(config: &Config) -> BTreeMap<String, String> {
+    let mut vars = BTreeMap::new();
+    let filenames = [
+        String::from(".env"),
+        String::from(".env.local"),
+        format!(".env.{}", config.mode),
+        format!(".env.{}.local", config.mode),
+    ];
+
+    for filename in &filenames {
+        if let Ok(contents) = fs::read_to_string(config.project_root.join(filename)) {
+            vars.extend(parse_env(&contents));
+        }
+    }
+
+    for (key, value) in env::vars() {
+        if vars.contains_key(&key) || key.starts_with(&config.env_prefix) {
+            vars.insert(key, value);
+        }
+    }
+
+    vars
+}
+
+/// Creates the define entries for `import.meta.env`. Only variables starting
+/// with `env_prefix` are exposed so secrets are never inlined into a chunk.
+pub fn import_meta_env_defines(config: &Config) -> HashMap<String, String> {
+    let mut public_vars: BTreeMap<String, String> = load_env(config)
+        .into_iter()
+        .filter(|(key, _)| key.starts_with(&config.env_prefix))
+        .map(|(key, value)| (key, to_js_string(&value)))
+        .collect();
+
+    let is_production = config.mode == "production";
+    public_vars.insert(String::from("MODE"), to_js_string(&config.mode));
+    public_vars.insert(String::from("DEV"), (!is_production).to_string());
+    public_vars.insert(String::from("PROD"), is_production.to_string());
+
+    let mut defines: HashMap<String, String> = public_vars
+        .iter()
+        .map(|(key, value)| (format!("import.meta.env.{}", key), value.clone()))
+        .collect();
+
+    let env_object = public_vars
+        .iter()
+        .map(|(key, value)| format!("{}: {}", to_js_string(key), value))
+        .collect::<Vec<String>>()
+        .join(", ");
+    defines.insert(
+        String::from("import.meta.env"),
+        format!("{{ {} }}", env_object),
+    );
+
+    defines
+}
+
+fn parse_env(contents: &str) -> Vec<(String, String)> {
+    let mut vars = vec![];
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+
+        let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            value[1..value.len() - 1]
+                .replace("\\n", "\n")
+                .replace("\\\"", "\"")
+        } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+            value[1..value.len() - 1].to_string()
+        } else {
+            // Strip trailing comments from unquoted values
+            match value.find(" #") {
+                Some(index) => value[..index].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+
+        vars.push((key.to_string(), value));
+    }
+
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_variables() {
+        assert_eq!(
+            parse_env("A=1\n  B = two  \nexport C=3\n"),
+            vars(&[("A", "1"), ("B", "two"), ("C", "3")])
+        );
+    }
+
+    #[test]
+    fn skips_comments_blank_and_invalid_lines() {
+        assert_eq!(
+            parse_env("# comment\n\nNOT_A_VARIABLE\nA=1 # trailing\nB=a#b\n"),
+            vars(&[("A", "1"), ("B", "a#b")])
+        );
+    }
+
+    #[test]
+    fn parses_quoted_values() {
+        assert_eq!(
+            parse_env("A=\"line\\nbreak \\\"quoted\\\" # kept\"\nB='single \\n # kept'\nC=\"\"\n"),
+            vars(&[
+                ("A", "line\nbreak \"quoted\" # kept"),
+                ("B", "single \\n # kept"),
+                ("C", "")
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_equals_signs_in_values() {
+        assert_eq!(parse_env("URL=a=b"), vars(&[("URL", "a=b")]));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::module_loader::can_resolve;
+use crate::{Compilation, Config, Platform};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// A request left out of the bundle, loaded natively at runtime or read from
+/// a global variable.
+#[derive(Clone)]
+pub struct External {
+    pub pattern: ExternalPattern,
+    /// JS expression the module is read from instead of a native `require`/`import`
+    pub global: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum ExternalPattern {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl External {
+    /// Parses an external from the CLI. `react` matches exactly, `lodash/*`
+    /// matches by prefix and `/^@org\//` is a regex. A `=Global` suffix reads
+    /// the module from a global variable, e.g. `react=React`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (pattern, global) = match value.rsplit_once('=') {
+            Some((pattern, global)) if !global.is_empty() && !global.ends_with('/') => {
+                (pattern, Some(global.to_string()))
+            }
+            _ => (value, None),
+        };
+
+        let pattern = if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+            let regex = Regex::new(&pattern[1..pattern.len() - 1])
+                .map_err(|err| format!("Invalid external {}: {}", value, err))?;
+            ExternalPattern::Regex(regex)
+        } else if let Some(prefix) = pattern.strip_suffix('*') {
+            ExternalPattern::Prefix(prefix.to_string())
+        } else {
+            ExternalPattern::Exact(pattern.to_string())
+        };
+
+        Ok(Self { pattern, global })
+    }
+
+    pub fn matches(&self, request: &str) -> bool {
+        match &self.pattern {
+            ExternalPattern::Exact(name) => request == name,
+            ExternalPattern::Prefix(prefix) => request.starts_with(prefix.as_str()),
+            ExternalPattern::Regex(regex) => regex.is_match(request),
+        }
+    }
+}
+
+pub fn find_external<'a>(externals: &'a [External], request: &str) -> Option<&'a External> {
+    externals.iter().find(|external| external.matches(request))
+}
+
+/// The external requests made by the modules of a chunk, in a stable order
+pub fn get_chunk_externals<'a>(c: &'a Compilation, module_ids: &[&String]) -> BTreeSet<&'a String> {
+    module_ids
+        .iter()
+        .filter_map(|module_id| c.external_requests.get(*module_id))
+        .flatten()
+        .collect()
+}
+
+/// Externals are left out of the bundle and never resolved. Node built-ins are
+/// always external when targeting Node.
+pub fn is_external(config: &Config, request: &str) -> bool {
+    (config.platform == Platform::Node && is_node_builtin(request))
+        || find_external(&config.externals, request).is_some()
+}
+
+/// Returns the polyfill request for a Node built-in when bundling for the
+/// browser, which is the configured polyfill package or otherwise the npm
+/// package of the same name, e.g. `buffer/`. The trailing slash stops it
+/// resolving to the built-in itself. Returns `None` if the polyfill can't be
+/// resolved from `source_filepath`, e.g. when the package isn't installed.
+pub fn find_polyfill(config: &Config, request: &str, source_filepath: &Path) -> Option<String> {
+    if config.platform != Platform::Browser || !is_node_builtin(request) {
+        return None;
+    }
+
+    let builtin = request.trim_start_matches("node:");
+    let polyfill = config
+        .polyfills
+        .get(builtin)
+        .cloned()
+        .unwrap_or_else(|| format!("{}/", builtin));
+
+    if can_resolve(source_filepath, &polyfill) {
+        Some(polyfill)
+    } else {
+        None
+    }
+}
+
+pub fn is_node_builtin(request: &str) -> bool {
+    request.starts_with("node:") || NODE_BUILTINS.contains(&request)
+}
+
+const NODE_BUILTINS: &[&str] = &[
+    "assert",
+    "assert/strict",
+    "async_hooks",
+    "buffer",
+    "child_process",
+    "cluster",
+    "console",
+    "constants",
+    "crypto",
+    "dgram",
+    "diagnostics_channel",
+    "dns",
+    "dns/promises",
+    "domain",
+    "events",
+    "fs",
+    "fs/promises",
+    "http",
+    "http2",
+    "https",
+    "inspector",
+    "module",
+    "net",
+    "os",
+    "path",
+    "path/posix",
+    "path/win32",
+    "perf_hooks",
+    "process",
+    "punycode",
+    "querystring",
+    "readline",
+    "repl",
+    "stream",
+    "stream/promises",
+    "stream/web",
+    "string_decoder",
+    "sys",
+    "timers",
+    "timers/promises",
+    "tls",
+    "trace_events",
+    "tty",
+    "url",
+    "util",
+    "util/types",
+    "v8",
+    "vm",
+    "wasi",
+    "worker_threads",
+    "zlib",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_exact_names() {
+        let external = External::parse("react").unwrap();
+        assert!(external.matches("react"));
+        assert!(!external.matches("react-dom"));
+        assert!(external.global.is_none());
+    }
+
+    #[test]
+    fn parses_prefixes() {
+        let external = External::parse("lodash/*").unwrap();
+        assert!(external.matches("lodash/get"));
+        assert!(!external.matches("lodash"));
+    }
+
+    #[test]
+    fn parses_regexes() {
+        let external = External::parse("/^@org//").unwrap();
+        assert!(external.matches("@org/ui"));
+        assert!(!external.matches("@other/ui"));
+
+        assert!(External::parse("/(/").is_err());
+    }
+
+    #[test]
+    fn parses_globals() {
+        let external = External::parse("react=React").unwrap();
+        assert!(external.matches("react"));
+        assert_eq!(external.global.as_deref(), Some("React"));
+
+        let external = External::parse("/^a=b$/").unwrap();
+        assert!(external.matches("a=b"));
+        assert!(external.global.is_none());
+
+        let external = External::parse("/^a=b$/=AB").unwrap();
+        assert!(external.matches("a=b"));
+        assert_eq!(external.global.as_deref(), Some("AB"));
+    }
+
+    #[test]
+    fn finds_polyfills_that_resolve_for_the_browser() {
+        let source_filepath =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/react/src/index.tsx");
+        let mut config = Config {
+            platform: Platform::Browser,
+            ..Config::default()
+        };
+        config
+            .polyfills
+            .insert(String::from("path"), String::from("./App"));
+
+        assert_eq!(
+            find_polyfill(&config, "node:path", &source_filepath).as_deref(),
+            Some("./App")
+        );
+        assert_eq!(find_polyfill(&config, "./path", &source_filepath), None);
+
+        config.platform = Platform::Node;
+        assert_eq!(find_polyfill(&config, "path", &source_filepath), None);
+    }
+
+    #[test]
+    fn skips_polyfills_that_are_not_installed() {
+        let source_filepath =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/react/src/index.tsx");
+        let mut config = Config {
+            platform: Platform::Browser,
+            ..Config::default()
+        };
+        assert_eq!(find_polyfill(&config, "fs", &source_filepath), None);
+
+        config
+            .polyfills
+            .insert(String::from("fs"), String::from("missing-fs-polyfill"));
+        assert_eq!(find_polyfill(&config, "fs", &source_filepath), None);
+    }
+}
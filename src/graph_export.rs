@@ -0,0 +1,229 @@
+use crate::js_module::{Dependency, ImportType, ModuleType};
+use crate::module_graph::ModuleGraph;
+use crate::utils::to_js_string;
+
+/// Renders the module graph as a Graphviz digraph. Entrypoints are drawn in
+/// bold and async edges are dashed.
+pub fn render_dot(graph: &ModuleGraph) -> String {
+    let mut dot = String::from("digraph modules {\n  node [shape=box];\n");
+
+    for module_id in sorted_module_ids(graph) {
+        let module = &graph.modules[module_id];
+        let style = if graph.entrypoints.contains(module_id) {
+            ", style=bold"
+        } else {
+            ""
+        };
+        dot.push_str(&format!(
+            "  {} [label={}{}];\n",
+            to_js_string(module_id),
+            to_js_string(&format!(
+                "{}\n{}",
+                module_id,
+                module_type_name(&module.module_type)
+            )),
+            style
+        ));
+    }
+
+    for module_id in sorted_module_ids(graph) {
+        for edge in graph.get_dependencies(module_id) {
+            let kinds: Vec<&str> = edge
+                .dependencies
+                .iter()
+                .map(|dependency| import_kind(&dependency.import_type))
+                .collect();
+            let style = if edge.is_async() {
+                ", style=dashed"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "  {} -> {} [label={}{}];\n",
+                to_js_string(module_id),
+                to_js_string(&edge.module_id),
+                to_js_string(&kinds.join(", ")),
+                style
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders the module graph as JSON with its entrypoints, modules and every
+/// edge between them, including each import along an edge.
+pub fn render_json(graph: &ModuleGraph) -> String {
+    let entrypoints: Vec<String> = graph
+        .entrypoints
+        .iter()
+        .map(|id| to_js_string(id))
+        .collect();
+
+    let modules: Vec<String> = sorted_module_ids(graph)
+        .into_iter()
+        .map(|module_id| {
+            format!(
+                "    {{\"id\": {}, \"type\": {}}}",
+                to_js_string(module_id),
+                to_js_string(module_type_name(&graph.modules[module_id].module_type))
+            )
+        })
+        .collect();
+
+    let mut edges: Vec<String> = vec![];
+    for module_id in sorted_module_ids(graph) {
+        for edge in graph.get_dependencies(module_id) {
+            let imports: Vec<String> = edge.dependencies.iter().map(render_import_json).collect();
+            edges.push(format!(
+                "    {{\"from\": {}, \"to\": {}, \"async\": {}, \"imports\": [{}]}}",
+                to_js_string(module_id),
+                to_js_string(&edge.module_id),
+                edge.is_async(),
+                imports.join(", ")
+            ));
+        }
+    }
+
+    format!(
+        "{{\n  \"entrypoints\": [{}],\n  \"modules\": [\n{}\n  ],\n  \"edges\": [\n{}\n  ]\n}}\n",
+        entrypoints.join(", "),
+        modules.join(",\n"),
+        edges.join(",\n")
+    )
+}
+
+fn render_import_json(dependency: &Dependency) -> String {
+    let names: Vec<String> = match &dependency.import_type {
+        ImportType::Default(_) => vec![to_js_string("default")],
+        ImportType::Named(named) => named
+            .iter()
+            .map(|named_import| to_js_string(&named_import.import_name))
+            .collect(),
+        _ => vec![],
+    };
+
+    format!(
+        "{{\"request\": {}, \"kind\": {}, \"names\": [{}]}}",
+        to_js_string(&dependency.request),
+        to_js_string(import_kind(&dependency.import_type)),
+        names.join(", ")
+    )
+}
+
+fn sorted_module_ids(graph: &ModuleGraph) -> Vec<&String> {
+    let mut module_ids: Vec<&String> = graph.modules.keys().collect();
+    module_ids.sort();
+    module_ids
+}
+
+fn module_type_name(module_type: &ModuleType) -> &'static str {
+    match module_type {
+        ModuleType::ESM => "esm",
+        ModuleType::CommonJS => "cjs",
+    }
+}
+
+fn import_kind(import_type: &ImportType) -> &'static str {
+    match import_type {
+        ImportType::Default(_) => "default",
+        ImportType::Namespace(_) => "namespace",
+        ImportType::Named(_) => "named",
+        ImportType::SideEffect => "side-effect",
+        ImportType::Require => "require",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js_module::{JsModule, ModuleExports, NamedImport};
+    use std::path::PathBuf;
+    use swc_common::DUMMY_SP;
+
+    fn create_module(id: &str, module_type: ModuleType) -> JsModule {
+        JsModule {
+            id: id.to_string(),
+            filepath: PathBuf::from(id),
+            code: String::new(),
+            original_size: 0,
+            module_type,
+            asset: None,
+            exports: ModuleExports::default(),
+            shims: vec![],
+            has_top_level_await: false,
+        }
+    }
+
+    fn create_dependency(import_type: ImportType, is_async: bool) -> Dependency {
+        Dependency {
+            request: "./dep".into(),
+            import_type,
+            span: DUMMY_SP,
+            is_async,
+            loc: None,
+        }
+    }
+
+    fn create_graph() -> ModuleGraph {
+        let mut graph = ModuleGraph::new();
+        graph.add_module(create_module("index.js", ModuleType::ESM));
+        graph.add_module(create_module("dep.js", ModuleType::CommonJS));
+        graph.add_module(create_module("lazy.js", ModuleType::ESM));
+        graph.add_entrypoint(String::from("index.js"));
+        graph.add_dependency(
+            "index.js",
+            "dep.js",
+            create_dependency(
+                ImportType::Named(vec![NamedImport {
+                    local: "b".into(),
+                    import_name: "a".into(),
+                }]),
+                false,
+            ),
+        );
+        graph.add_dependency(
+            "index.js",
+            "lazy.js",
+            create_dependency(ImportType::Require, true),
+        );
+        graph
+    }
+
+    #[test]
+    fn renders_dot() {
+        assert_eq!(
+            render_dot(&create_graph()),
+            "digraph modules {
+  node [shape=box];
+  \"dep.js\" [label=\"dep.js\\ncjs\"];
+  \"index.js\" [label=\"index.js\\nesm\", style=bold];
+  \"lazy.js\" [label=\"lazy.js\\nesm\"];
+  \"index.js\" -> \"dep.js\" [label=\"named\"];
+  \"index.js\" -> \"lazy.js\" [label=\"require\", style=dashed];
+}
+"
+        );
+    }
+
+    #[test]
+    fn renders_json() {
+        assert_eq!(
+            render_json(&create_graph()),
+            "{
+  \"entrypoints\": [\"index.js\"],
+  \"modules\": [
+    {\"id\": \"dep.js\", \"type\": \"cjs\"},
+    {\"id\": \"index.js\", \"type\": \"esm\"},
+    {\"id\": \"lazy.js\", \"type\": \"esm\"}
+  ],
+  \"edges\": [
+    {\"from\": \"index.js\", \"to\": \"dep.js\", \"async\": false, \"imports\": [{\"request\": \"./dep\", \"kind\": \"named\", \"names\": [\"a\"]}]},
+    {\"from\": \"index.js\", \"to\": \"lazy.js\", \"async\": true, \"imports\": [{\"request\": \"./dep\", \"kind\": \"require\", \"names\": []}]}
+  ]
+}
+"
+        );
+    }
+}
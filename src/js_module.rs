@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 use swc_atoms::JsWord;
+use swc_common::Span;
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum ModuleType {
     ESM,
     CommonJS,
@@ -10,7 +12,27 @@ pub struct JsModule {
     pub id: String,
     pub filepath: PathBuf,
     pub code: String,
+    /// Size in bytes of the source file before any transforms
+    pub original_size: u64,
     pub module_type: ModuleType,
+    pub asset: Option<Asset>,
+    pub exports: ModuleExports,
+    /// Node globals declared for the browser, e.g. `process`
+    pub shims: Vec<String>,
+    pub has_top_level_await: bool,
+}
+
+/// A file copied as-is to the output directory alongside the chunk
+pub struct Asset {
+    pub filename: String,
+}
+
+/// The exports of an ES module, known at build time
+#[derive(Default)]
+pub struct ModuleExports {
+    pub names: Vec<JsWord>,
+    /// Requests re-exported with `export * from`
+    pub star_requests: Vec<JsWord>,
 }
 
 #[derive(Clone)]
@@ -32,11 +54,25 @@ pub enum ImportType {
 pub struct Dependency {
     pub request: JsWord,
     pub import_type: ImportType,
+    /// Location of the import in the source module
+    pub span: Span,
+    /// Async dependencies are loaded on demand rather than before the importer runs
+    pub is_async: bool,
+    /// Line and column of `span`, resolved once the module is parsed
+    pub loc: Option<Location>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Location {
+    /// 1-based
+    pub line: usize,
+    /// 0-based, in chars
+    pub column: usize,
 }
 
 impl JsModule {
     pub fn update_dep_src(&mut self, request: &str, dep_id: &str) {
         // TODO: Hardcoded to max 5 replaces, should be equal to the amount of required replaces
-        self.code = self.code.replacen(&request, &dep_id, 5);
+        self.code = self.code.replacen(request, dep_id, 5);
     }
 }
@@ -1,43 +1,407 @@
+mod chunks;
 mod diagnostics;
+mod env;
+mod externals;
+mod graph_export;
 mod js_module;
+mod loaders;
 mod module_graph;
 mod module_loader;
 mod parser;
+mod stats;
 mod template;
 mod transforms;
 mod utils;
+mod watch;
 
-use crate::diagnostics::Diagnostics;
+use crate::chunks::{create_chunk, split_chunks};
+use crate::diagnostics::{CircularDependency, Diagnostic, Diagnostics};
+use crate::externals::find_external;
+pub use crate::externals::{External, ExternalPattern};
+use crate::loaders::worker::name_worker_chunks;
 use crate::module_graph::ModuleGraph;
+use crate::stats::{render_stats_html, render_stats_json, ChunkStats};
 use crate::utils::create_module_id;
+pub use crate::utils::is_global_name;
+use crate::watch::{get_watched_paths, take_snapshot, WatchedPaths};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
+/// How the entry's exports are exposed by the emitted chunk
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// An ES module re-exporting the entry's exports
+    Esm,
+    /// Assigns the entry's exports to `module.exports`
+    Cjs,
+    /// Works with AMD, CommonJS or as a global named `global_name`
+    Umd,
+    /// A self-executing script, optionally assigning to a global `global_name`
+    Iife,
+}
+
+/// The environment the bundle runs in
+#[derive(Clone, Copy, PartialEq)]
+pub enum Platform {
+    /// Node built-ins are external and modules get `__filename` and `__dirname`
+    Node,
+    Browser,
+    /// Makes no assumptions about the environment
+    Neutral,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub project_root: PathBuf,
     pub entrypoint: PathBuf,
     pub output_dir: PathBuf,
+    /// URL prefix the output directory is served from, see `public_path()`
+    pub public_path: Option<String>,
+    /// File extensions emitted to the output directory when imported
+    pub asset_extensions: Vec<String>,
+    /// Assets smaller than this many bytes are inlined as data URIs
+    pub asset_inline_limit: u64,
+    /// Expressions replaced at compile time, keyed by identifier or member
+    /// path with a JS expression as the value, e.g. `process.env.NODE_ENV`
+    pub define: HashMap<String, String>,
+    /// Selects which `.env.[mode]` files are loaded, and is the value of
+    /// `process.env.NODE_ENV` unless it's in `define`
+    pub mode: String,
+    /// Only env variables with this prefix are exposed on `import.meta.env`
+    pub env_prefix: String,
+    /// Adds a warning for each import cycle in the module graph
+    pub warn_circular: bool,
+    /// Writes `stats.json`, a report of chunk and module sizes, to the output dir
+    pub stats: bool,
+    /// Also writes `stats.html`, a treemap of the report
+    pub stats_html: bool,
+    pub format: OutputFormat,
+    /// Global variable the entry's exports are assigned to by UMD and IIFE chunks,
+    /// an identifier or a dotted path like `MyLib.widgets`
+    pub global_name: Option<String>,
+    /// Requests that are never bundled
+    pub externals: Vec<External>,
+    pub platform: Platform,
+    /// Node globals declared for browser modules that reference them, any of
+    /// `global`, `process` and `Buffer`
+    pub node_globals: Vec<String>,
+    /// Browser polyfill packages for Node built-ins, keyed by built-in name
+    pub polyfills: HashMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            project_root: PathBuf::new(),
+            entrypoint: PathBuf::new(),
+            output_dir: PathBuf::new(),
+            public_path: None,
+            asset_extensions: [
+                "png", "jpg", "jpeg", "gif", "svg", "webp", "avif", "ico", "woff", "woff2", "ttf",
+                "otf", "eot",
+            ]
+            .iter()
+            .map(|ext| ext.to_string())
+            .collect(),
+            asset_inline_limit: 0,
+            define: HashMap::new(),
+            mode: String::from("production"),
+            env_prefix: String::from("DUCTTAPE_"),
+            warn_circular: false,
+            stats: false,
+            stats_html: false,
+            format: OutputFormat::Iife,
+            global_name: None,
+            externals: vec![],
+            platform: Platform::Browser,
+            node_globals: ["global", "process", "Buffer"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            polyfills: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the configured public path, or derives it from the output dir
+    /// assuming the project root is served at `/`. Falls back to `/` for an
+    /// output dir outside of the project root.
+    pub fn public_path(&self) -> String {
+        if let Some(public_path) = &self.public_path {
+            return public_path.clone();
+        }
+
+        let relative_dir = match self.output_dir.strip_prefix(&self.project_root) {
+            Ok(relative_dir) => relative_dir,
+            Err(_) => return String::from("/"),
+        };
+
+        let mut public_path = String::from("/");
+        for component in relative_dir.components() {
+            match component {
+                Component::Normal(name) => {
+                    public_path.push_str(&name.to_string_lossy());
+                    public_path.push('/');
+                }
+                Component::CurDir => {}
+                _ => return String::from("/"),
+            }
+        }
+        public_path
+    }
 }
 
 pub struct Compilation {
     pub config: Config,
     pub diagnostics: Diagnostics,
     pub graph: ModuleGraph,
+    /// Requests matched by `config.externals`, by the id of the module making them
+    pub external_requests: HashMap<String, BTreeSet<String>>,
 }
 
-pub fn compile(config: Config) {
+pub fn compile(config: Config) -> Compilation {
     println!(
         "Entrypoint: '{}'\nOutput dir: '{}'\nProject root: '{}'",
         config.entrypoint.to_str().unwrap(),
         config.output_dir.to_str().unwrap(),
         config.project_root.to_str().unwrap()
     );
+    if let Some(global_name) = &config.global_name {
+        assert!(
+            is_global_name(global_name),
+            "Global name '{}' must be an identifier or a dotted path like 'MyLib.widgets'",
+            global_name
+        );
+    }
     let start_time = Instant::now();
+    let mut c = load_graph(config);
+    check_externals(&c);
+
+    if c.config.warn_circular {
+        let mut cycles = vec![];
+        for entry_id in &c.graph.entrypoints {
+            for cycle in c.graph.find_cycles(entry_id) {
+                let cycle: Vec<String> = cycle.into_iter().cloned().collect();
+                // Modules shared with a worker would otherwise be reported twice
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+            }
+        }
+        for cycle in cycles {
+            c.diagnostics
+                .add_diagnostic(Diagnostic::CircularDependency(CircularDependency { cycle }));
+        }
+    }
+
+    // The first entrypoint is the main chunk, the rest are workers it creates
+    let (main_id, worker_ids) = c
+        .graph
+        .entrypoints
+        .split_first()
+        .expect("Missing entrypoint");
+    // Only ES modules can import each other's chunks, so other formats bundle
+    // every module imported with `import()` into the main chunk
+    let main_chunks = match c.config.format {
+        OutputFormat::Esm => split_chunks(&c.graph, main_id, String::from("main.js")),
+        _ => vec![create_chunk(&c.graph, main_id, String::from("main.js"))],
+    };
+    let (entry_chunk, split_chunks) = main_chunks.split_first().expect("Missing entry chunk");
+    let mut rendered_chunks: Vec<String> =
+        iter::once(template::render_chunk(entry_chunk, split_chunks, &c))
+            .chain(
+                split_chunks
+                    .iter()
+                    .map(|chunk| template::render_split_chunk(chunk, main_id, &c)),
+            )
+            .collect();
+    let worker_chunks = worker_ids
+        .iter()
+        .map(|worker_id| {
+            (
+                worker_id.as_str(),
+                template::render_worker_chunk(worker_id, &c),
+            )
+        })
+        .collect();
+    let worker_chunks = name_worker_chunks(&mut rendered_chunks, worker_chunks);
+    emit_assets(&c).expect("Failed to write assets");
+
+    let main_chunks = main_chunks
+        .iter()
+        .zip(rendered_chunks)
+        .map(|(chunk, content)| (chunk.filename.clone(), content, chunk.modules.clone()));
+    let worker_chunks =
+        worker_ids
+            .iter()
+            .zip(worker_chunks)
+            .map(|(worker_id, (filename, content))| {
+                (filename, content, c.graph.get_modules_in_order(worker_id))
+            });
+
+    let mut chunks = vec![];
+    for (filename, chunk, modules) in main_chunks.chain(worker_chunks) {
+        let output_filepath = c.config.output_dir.join(&filename);
+        emit_file(output_filepath.to_str().unwrap(), &chunk).expect("Failed to write chunk");
+        chunks.push(ChunkStats {
+            filename,
+            size: chunk.len(),
+            modules,
+        });
+    }
+    let elapsed_time = start_time.elapsed();
+
+    if c.config.stats || c.config.stats_html {
+        let stats_json = render_stats_json(&c, &chunks, elapsed_time);
+        let stats_filepath = c.config.output_dir.join("stats.json");
+        emit_file(stats_filepath.to_str().unwrap(), &stats_json).expect("Failed to write stats");
+
+        if c.config.stats_html {
+            let html_filepath = c.config.output_dir.join("stats.html");
+            emit_file(
+                html_filepath.to_str().unwrap(),
+                &render_stats_html(&stats_json),
+            )
+            .expect("Failed to write stats");
+        }
+    }
+
+    c.diagnostics.print();
+    println!("Build complete in {}ms", elapsed_time.as_millis());
+    c
+}
+
+/// How often watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Compiles the bundle, then compiles it again whenever a module's file
+/// changes or a file is added to or removed from the directory of a glob or
+/// context module, so its matches are expanded again.
+pub fn watch(config: Config) {
+    // Until a compilation succeeds only the entrypoint is known
+    let mut paths = WatchedPaths {
+        files: vec![config.entrypoint.clone()],
+        dirs: vec![],
+    };
+
+    loop {
+        // A failed compilation keeps watching the files of the last one that
+        // succeeded, after its panic message is printed
+        if let Ok(c) = panic::catch_unwind(AssertUnwindSafe(|| compile(config.clone()))) {
+            paths = get_watched_paths(&c);
+        }
+
+        let snapshot = take_snapshot(&paths);
+        println!("Watching {} files for changes", snapshot.len());
+        while take_snapshot(&paths) == snapshot {
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
+
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/// Prints the module graph in the given format without emitting the bundle
+pub fn export_graph(config: Config, format: GraphFormat) {
+    let c = load_graph(config);
+    let output = match format {
+        GraphFormat::Dot => graph_export::render_dot(&c.graph),
+        GraphFormat::Json => graph_export::render_json(&c.graph),
+    };
+    print!("{}", output);
+}
+
+/// Prints every import chain from the entrypoint to `module`, which is either
+/// a path relative to the project root or a module id, without emitting anything.
+pub fn why(config: Config, module: &str) {
+    let c = load_graph(config);
+    let module_path = c.config.project_root.join(module);
+    let module_id = if c.graph.has_module(module) {
+        module.to_string()
+    } else {
+        create_module_id(&module_path, &c.config.project_root)
+    };
+
+    if !c.graph.has_module(&module_id) {
+        println!("Module {} is not included in the bundle", module);
+        return;
+    }
+
+    let chains = c.graph.find_import_chains(&module_id);
+    for (index, chain) in chains.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+
+        println!("{}", chain[0]);
+        for hop in chain.windows(2) {
+            let edge = c
+                .graph
+                .get_dependency_edge(hop[0], hop[1])
+                .expect("Missing dependency edge");
+
+            // A single import declaration can add several dependencies
+            let mut imports: Vec<String> = edge
+                .dependencies
+                .iter()
+                .map(|dependency| match dependency.loc {
+                    Some(loc) => format!(
+                        "  imports \"{}\" at {}:{}:{}",
+                        dependency.request,
+                        hop[0],
+                        loc.line,
+                        loc.column + 1
+                    ),
+                    None => format!("  imports \"{}\"", dependency.request),
+                })
+                .collect();
+            imports.dedup();
+
+            for import in imports {
+                println!("{}", import);
+            }
+            println!("{}", hop[1]);
+        }
+    }
+}
+
+/// Browser scripts have no native way to load an external, so each one they
+/// use has to be read from a global
+fn check_externals(c: &Compilation) {
+    let format = match (c.config.platform, c.config.format) {
+        (Platform::Browser, OutputFormat::Iife) => "iife",
+        (Platform::Browser, OutputFormat::Umd) => "umd",
+        _ => return,
+    };
+
+    for request in c.external_requests.values().flatten() {
+        let global = find_external(&c.config.externals, request)
+            .and_then(|external| external.global.as_ref());
+        if global.is_none() {
+            panic!(
+                "External '{}' must be read from a global in {} output for the browser, e.g. --external {}=Global",
+                request, format, request
+            );
+        }
+    }
+}
+
+fn load_graph(config: Config) -> Compilation {
     let mut c = Compilation {
         diagnostics: Diagnostics::new(),
         graph: ModuleGraph::new(),
+        external_requests: HashMap::new(),
         config,
     };
 
@@ -47,13 +411,17 @@ pub fn compile(config: Config) {
     ));
 
     module_loader::load_entrypoint(&mut c);
+    c
+}
 
-    let chunk = template::render_chunk(&c.graph.entrypoints[0], &c);
-    let output_filepath = c.config.output_dir.join("main.js");
-    emit_file(&output_filepath.to_str().unwrap(), &chunk).expect("Failed to write chunk");
-    let elapsed_time = start_time.elapsed();
-    c.diagnostics.print();
-    println!("Build complete in {}ms", elapsed_time.as_millis());
+fn emit_assets(c: &Compilation) -> std::io::Result<()> {
+    fs::create_dir_all(&c.config.output_dir)?;
+    for module in c.graph.modules.values() {
+        if let Some(asset) = &module.asset {
+            fs::copy(&module.filepath, c.config.output_dir.join(&asset.filename))?;
+        }
+    }
+    Ok(())
 }
 
 fn emit_file(file_path: &str, contents: &str) -> std::io::Result<()> {
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+use super::LoadedModule;
+use crate::js_module::Asset;
+use crate::utils::{short_hash, to_js_string};
+use crate::Config;
+
+pub fn is_asset(filepath: &Path, config: &Config) -> bool {
+    match filepath.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => config
+            .asset_extensions
+            .iter()
+            .any(|asset_ext| asset_ext.eq_ignore_ascii_case(ext)),
+        None => false,
+    }
+}
+
+/// Default exports the public URL of the asset, or a data URI when the asset
+/// is smaller than `asset_inline_limit`.
+pub fn load_asset(filepath: &Path, config: &Config) -> Result<LoadedModule, String> {
+    let contents = read_asset(filepath)?;
+
+    if (contents.len() as u64) < config.asset_inline_limit {
+        Ok(inline_asset(filepath, &contents))
+    } else {
+        Ok(emit_asset(filepath, &contents, config))
+    }
+}
+
+pub fn read_asset(filepath: &Path) -> Result<Vec<u8>, String> {
+    fs::read(filepath).map_err(|_| format!("Failed to read file: {}", filepath.to_str().unwrap()))
+}
+
+pub fn inline_asset(filepath: &Path, contents: &[u8]) -> LoadedModule {
+    let data_uri = format!(
+        "data:{};base64,{}",
+        mime_type(filepath),
+        base64::encode(contents)
+    );
+
+    LoadedModule {
+        code: format!("export default {};\n", to_js_string(&data_uri)),
+        asset: None,
+        is_generated: true,
+    }
+}
+
+pub fn emit_asset(filepath: &Path, contents: &[u8], config: &Config) -> LoadedModule {
+    let filename = hashed_filename(filepath, contents);
+    let url = format!("{}{}", config.public_path(), filename);
+
+    LoadedModule {
+        code: format!("export default {};\n", to_js_string(&url)),
+        asset: Some(Asset { filename }),
+        is_generated: true,
+    }
+}
+
+fn hashed_filename(filepath: &Path, contents: &[u8]) -> String {
+    let stem = filepath
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("asset");
+
+    match filepath.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, short_hash(contents), ext),
+        None => format!("{}.{}", stem, short_hash(contents)),
+    }
+}
+
+fn mime_type(filepath: &Path) -> &'static str {
+    let ext = filepath
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "eot" => "application/vnd.ms-fontobject",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
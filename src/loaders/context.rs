@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::module_loader::RESOLVE_EXTENSIONS;
+use crate::utils::to_js_string;
+
+/// Context requests, e.g. `./locales/?context=*.json`, are created for
+/// requires built from a relative directory and a dynamic part
+pub fn is_context_query(query: &str) -> bool {
+    query.starts_with("context=")
+}
+
+/// Emits a CommonJS module exporting a require function for every file in
+/// `dir` matching `glob`. Files are keyed by their path relative to `dir`,
+/// with and without a resolvable extension.
+pub fn context_to_js(dir: &Path, glob: &str) -> Result<String, String> {
+    let dir_name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid context directory: {}", dir.to_str().unwrap()))?;
+
+    let mut map = String::new();
+    for relative_path in find_files(dir)? {
+        // Requests are resolved from the context module's parent directory
+        let request = to_js_string(&format!("./{}/{}", dir_name, relative_path));
+
+        let mut keys = vec![relative_path.clone()];
+        if let Some((stem, ext)) = relative_path.rsplit_once('.') {
+            if RESOLVE_EXTENSIONS.contains(&ext) && !stem.ends_with('/') {
+                keys.push(stem.to_string());
+            }
+        }
+
+        for key in keys.iter().filter(|key| matches_wildcard(glob, key)) {
+            map.push_str(&format!(
+                "  {}: function () {{ return require({}); }},\n",
+                to_js_string(&format!("./{}", key)),
+                request
+            ));
+        }
+    }
+
+    Ok(format!(
+        "var map = {{\n{}}};
+function requireContext(request) {{
+  if (!Object.prototype.hasOwnProperty.call(map, request)) {{
+    var error = new Error(\"Cannot find module '\" + request + \"'\");
+    error.code = 'MODULE_NOT_FOUND';
+    throw error;
+  }}
+  return map[request]();
+}}
+requireContext.keys = function () {{
+  return Object.keys(map);
+}};
+module.exports = requireContext;
+",
+        map
+    ))
+}
+
+/// Returns the path of every file under `dir` relative to it, with `/`
+/// separators and sorted so generated modules are stable between builds.
+/// `node_modules` directories are skipped.
+pub fn find_files(dir: &Path) -> Result<Vec<String>, String> {
+    let mut files = vec![];
+    walk_dir(dir, &mut files)
+        .map_err(|_| format!("Failed to read directory: {}", dir.to_str().unwrap()))?;
+
+    let mut relative_paths: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let components: Vec<_> = file
+                .strip_prefix(dir)
+                .unwrap()
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect();
+            components.join("/")
+        })
+        .collect();
+    relative_paths.sort();
+    Ok(relative_paths)
+}
+
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name != "node_modules") {
+                walk_dir(&path, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Matches `value` against a pattern where `*` matches any characters,
+/// including `/`, as the dynamic part of a require can span directories.
+/// Unlike `glob::matches_path_glob`, there's no `**`.
+fn matches_wildcard(glob: &str, value: &str) -> bool {
+    let parts: Vec<&str> = glob.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+
+    if parts.len() == 1 {
+        return glob == value;
+    }
+    if value.len() < first.len() + last.len() || !value.starts_with(first) || !value.ends_with(last)
+    {
+        return false;
+    }
+
+    let mut rest = &value[first.len()..value.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_stars_across_directories() {
+        assert!(matches_wildcard("*.json", "en.json"));
+        assert!(matches_wildcard("*.json", "nested/en.json"));
+        assert!(matches_wildcard("*", "en"));
+        assert!(!matches_wildcard("*.json", "en.js"));
+    }
+
+    #[test]
+    fn matches_stars_between_parts() {
+        assert!(matches_wildcard("locale-*-*.json", "locale-en-gb.json"));
+        assert!(!matches_wildcard("locale-*-*.json", "locale-en.json"));
+        assert!(!matches_wildcard("a*a", "a"));
+    }
+
+    #[test]
+    fn matches_exact_values() {
+        assert!(matches_wildcard("en.json", "en.json"));
+        assert!(!matches_wildcard("en.json", "fr.json"));
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::utils::{short_hash, to_js_string};
+
+#[derive(Clone)]
+enum ComposedClass {
+    Local(String),
+    Global(String),
+    Imported { request: String, name: String },
+}
+
+struct CssModuleCompiler {
+    suffix: String,
+    // Original class name -> classes composed into it, sorted for stable output
+    classes: BTreeMap<String, Vec<ComposedClass>>,
+}
+
+pub fn is_css_module(filepath: &Path) -> bool {
+    matches!(
+        filepath.file_name().and_then(|name| name.to_str()),
+        Some(name) if name.ends_with(".module.css")
+    )
+}
+
+/// Scopes every class selector in `css` to the module and returns an ES module
+/// which injects the scoped styles and default exports the class name map.
+pub fn css_module_to_js(css: &str, module_id: &str) -> String {
+    let mut compiler = CssModuleCompiler {
+        suffix: short_hash(module_id.as_bytes()),
+        classes: BTreeMap::new(),
+    };
+    let scoped_css = compiler.process_rules(css);
+
+    compiler.render(&scoped_css, module_id)
+}
+
+impl CssModuleCompiler {
+    fn scoped_name(&self, name: &str) -> String {
+        format!("{}_{}", name, self.suffix)
+    }
+
+    fn process_rules(&mut self, input: &str) -> String {
+        let bytes = input.as_bytes();
+        let mut output = String::with_capacity(input.len());
+        let mut pos = 0;
+
+        while pos < input.len() {
+            let prelude_end = match find_top_level(input, pos, b"{;") {
+                Some(end) => end,
+                None => {
+                    output.push_str(&input[pos..]);
+                    break;
+                }
+            };
+            let prelude = &input[pos..prelude_end];
+
+            if bytes[prelude_end] == b';' {
+                // At-rule statement, e.g. @import or @charset
+                output.push_str(&input[pos..=prelude_end]);
+                pos = prelude_end + 1;
+                continue;
+            }
+
+            let block_end = find_block_end(input, prelude_end).unwrap_or(input.len());
+            let body = &input[prelude_end + 1..block_end];
+
+            match at_rule_name(prelude) {
+                Some(name) if is_conditional_group_rule(name) => {
+                    output.push_str(prelude);
+                    output.push('{');
+                    output.push_str(&self.process_rules(body));
+                    output.push('}');
+                }
+                Some(_) => {
+                    // @keyframes, @font-face etc. contain no class selectors
+                    output.push_str(&input[pos..(block_end + 1).min(input.len())]);
+                }
+                None => {
+                    let (selector, classes) = self.scope_selector(prelude);
+                    output.push_str(&selector);
+                    output.push('{');
+                    output.push_str(&self.process_declarations(body, &classes));
+                    output.push('}');
+                }
+            }
+
+            pos = block_end + 1;
+        }
+
+        output
+    }
+
+    fn scope_selector(&mut self, selector: &str) -> (String, Vec<String>) {
+        let bytes = selector.as_bytes();
+        let mut output = String::with_capacity(selector.len());
+        let mut classes = vec![];
+        let mut i = 0;
+
+        while i < selector.len() {
+            if let Some(next) = skip_comment_or_string(bytes, i) {
+                output.push_str(&selector[i..next]);
+                i = next;
+                continue;
+            }
+
+            if selector[i..].starts_with(":global(") {
+                let open = i + ":global".len();
+                let close = find_closing_paren(selector, open).unwrap_or(selector.len());
+                output.push_str(&selector[open + 1..close]);
+                i = close + 1;
+                continue;
+            }
+
+            if bytes[i] == b'.' {
+                let ident_end = read_ident(selector, i + 1);
+                if ident_end > i + 1 {
+                    let name = &selector[i + 1..ident_end];
+                    output.push('.');
+                    output.push_str(&self.scoped_name(name));
+                    self.classes.entry(name.to_string()).or_default();
+                    classes.push(name.to_string());
+                    i = ident_end;
+                    continue;
+                }
+            }
+
+            let c = selector[i..].chars().next().unwrap();
+            output.push(c);
+            i += c.len_utf8();
+        }
+
+        (output, classes)
+    }
+
+    fn process_declarations(&mut self, body: &str, classes: &[String]) -> String {
+        let mut output = String::with_capacity(body.len());
+        let mut pos = 0;
+
+        while pos < body.len() {
+            let end = find_top_level(body, pos, b";").map_or(body.len(), |end| end + 1);
+            let declaration = &body[pos..end];
+
+            match parse_composes(declaration) {
+                Some(composed) => {
+                    for class in classes {
+                        let entry = self.classes.get_mut(class).unwrap();
+                        entry.extend(composed.iter().cloned());
+                    }
+                }
+                None => output.push_str(declaration),
+            }
+
+            pos = end;
+        }
+
+        output
+    }
+
+    fn collect_composed<'a>(
+        &'a self,
+        class: &'a str,
+        names: &mut Vec<String>,
+        imported: &mut Vec<(&'a str, &'a str)>,
+        visited: &mut Vec<&'a str>,
+    ) {
+        let composed = match self.classes.get(class) {
+            Some(composed) => composed,
+            None => return,
+        };
+
+        for c in composed {
+            match c {
+                ComposedClass::Local(name) => {
+                    if !visited.contains(&name.as_str()) {
+                        visited.push(name);
+                        names.push(self.scoped_name(name));
+                        self.collect_composed(name, names, imported, visited);
+                    }
+                }
+                ComposedClass::Global(name) => {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+                ComposedClass::Imported { request, name } => {
+                    if !imported.contains(&(request, name)) {
+                        imported.push((request, name));
+                    }
+                }
+            }
+        }
+    }
+
+    fn render(&self, scoped_css: &str, module_id: &str) -> String {
+        let mut requests: Vec<&str> = vec![];
+        let mut class_map = String::new();
+
+        for class in self.classes.keys() {
+            let mut names = vec![self.scoped_name(class)];
+            let mut imported = vec![];
+            self.collect_composed(class, &mut names, &mut imported, &mut vec![class]);
+
+            let mut value = to_js_string(&names.join(" "));
+            for (request, name) in imported {
+                let index = match requests.iter().position(|r| *r == request) {
+                    Some(index) => index,
+                    None => {
+                        requests.push(request);
+                        requests.len() - 1
+                    }
+                };
+                value.push_str(&format!(
+                    " + \" \" + __composes_{}[{}]",
+                    index,
+                    to_js_string(name)
+                ));
+            }
+
+            class_map.push_str(&format!("  {}: {},\n", to_js_string(class), value));
+        }
+
+        let mut code = String::new();
+        // Composed modules are imported first so their styles are injected earlier
+        for (index, request) in requests.iter().enumerate() {
+            code.push_str(&format!(
+                "import __composes_{} from {};\n",
+                index,
+                to_js_string(request)
+            ));
+        }
+        code.push_str(&format!(
+            "var css = {};
+if (typeof document !== \"undefined\") {{
+  var style = document.createElement(\"style\");
+  style.setAttribute(\"data-module\", {});
+  style.textContent = css;
+  document.head.appendChild(style);
+}}
+export default {{
+{}}};
+",
+            to_js_string(scoped_css),
+            to_js_string(module_id),
+            class_map
+        ));
+
+        code
+    }
+}
+
+fn parse_composes(declaration: &str) -> Option<Vec<ComposedClass>> {
+    let declaration = declaration.trim().trim_end_matches(';');
+    let value = declaration.strip_prefix("composes")?.trim_start();
+    let value = value.strip_prefix(':')?.trim();
+
+    let (names, source) = match value.find(" from ") {
+        Some(index) => (
+            &value[..index],
+            Some(value[index + " from ".len()..].trim()),
+        ),
+        None => (value, None),
+    };
+
+    Some(
+        names
+            .split_whitespace()
+            .map(|name| match source {
+                None => ComposedClass::Local(name.to_string()),
+                Some("global") => ComposedClass::Global(name.to_string()),
+                Some(request) => ComposedClass::Imported {
+                    request: request.trim_matches(|c| c == '"' || c == '\'').to_string(),
+                    name: name.to_string(),
+                },
+            })
+            .collect(),
+    )
+}
+
+fn at_rule_name(prelude: &str) -> Option<&str> {
+    let bytes = prelude.as_bytes();
+    let mut i = 0;
+
+    // Skip any leading whitespace and comments
+    while i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+        } else if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            i = skip_comment_or_string(bytes, i).unwrap_or(bytes.len());
+        } else {
+            break;
+        }
+    }
+
+    if bytes.get(i) != Some(&b'@') {
+        return None;
+    }
+
+    Some(&prelude[i + 1..read_ident(prelude, i + 1)])
+}
+
+fn is_conditional_group_rule(name: &str) -> bool {
+    matches!(
+        name,
+        "media" | "supports" | "document" | "layer" | "container"
+    )
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-' || !c.is_ascii()
+}
+
+fn read_ident(input: &str, start: usize) -> usize {
+    let mut chars = input[start..].chars().peekable();
+
+    match chars.peek() {
+        Some(c) if c.is_ascii_digit() || !is_ident_char(*c) => return start,
+        None => return start,
+        _ => {}
+    }
+
+    let mut end = start;
+    for c in chars {
+        if !is_ident_char(c) {
+            break;
+        }
+        end += c.len_utf8();
+    }
+
+    end
+}
+
+/// Returns the index after the comment or string starting at `i`, if any.
+fn skip_comment_or_string(bytes: &[u8], i: usize) -> Option<usize> {
+    match bytes[i] {
+        b'/' if bytes.get(i + 1) == Some(&b'*') => {
+            let mut j = i + 2;
+            while j + 1 < bytes.len() && !(bytes[j] == b'*' && bytes[j + 1] == b'/') {
+                j += 1;
+            }
+            Some((j + 2).min(bytes.len()))
+        }
+        quote @ b'"' | quote @ b'\'' => {
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != quote {
+                if bytes[j] == b'\\' {
+                    j += 1;
+                }
+                j += 1;
+            }
+            Some((j + 1).min(bytes.len()))
+        }
+        _ => None,
+    }
+}
+
+fn find_top_level(input: &str, start: usize, targets: &[u8]) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+
+    while i < bytes.len() {
+        if let Some(next) = skip_comment_or_string(bytes, i) {
+            i = next;
+            continue;
+        }
+
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            b if depth == 0 && targets.contains(&b) => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn find_block_end(input: &str, open: usize) -> Option<usize> {
+    find_closing(input, open, b'{', b'}')
+}
+
+fn find_closing_paren(input: &str, open: usize) -> Option<usize> {
+    find_closing(input, open, b'(', b')')
+}
+
+fn find_closing(input: &str, open: usize, open_char: u8, close_char: u8) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+
+    while i < bytes.len() {
+        if let Some(next) = skip_comment_or_string(bytes, i) {
+            i = next;
+            continue;
+        }
+
+        if bytes[i] == open_char {
+            depth += 1;
+        } else if bytes[i] == close_char {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+        i += 1;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(css: &str) -> (String, CssModuleCompiler) {
+        let mut compiler = CssModuleCompiler {
+            suffix: String::from("abc"),
+            classes: BTreeMap::new(),
+        };
+        let scoped_css = compiler.process_rules(css);
+        (scoped_css, compiler)
+    }
+
+    #[test]
+    fn scopes_class_selectors() {
+        let (css, _) = compile(".title, .nav > .link:hover { color: red; }");
+        assert_eq!(
+            css,
+            ".title_abc, .nav_abc > .link_abc:hover { color: red; }"
+        );
+    }
+
+    #[test]
+    fn leaves_global_selectors_unscoped() {
+        let (css, compiler) = compile(":global(.dark) .title :global(.icon) {}");
+        assert_eq!(css, ".dark .title_abc .icon {}");
+        assert_eq!(compiler.classes.keys().collect::<Vec<_>>(), vec!["title"]);
+    }
+
+    #[test]
+    fn scopes_rules_nested_in_media_queries() {
+        let (css, _) = compile(
+            "@media (min-width: 600px) { @supports (display: grid) { .grid { display: grid; } } }",
+        );
+        assert_eq!(
+            css,
+            "@media (min-width: 600px) { @supports (display: grid) { .grid_abc { display: grid; } } }"
+        );
+    }
+
+    #[test]
+    fn leaves_keyframes_and_statements_unscoped() {
+        let (css, compiler) =
+            compile("@import \"reset.css\";@keyframes fade { from { opacity: 0.5; } }");
+        assert_eq!(
+            css,
+            "@import \"reset.css\";@keyframes fade { from { opacity: 0.5; } }"
+        );
+        assert!(compiler.classes.is_empty());
+    }
+
+    #[test]
+    fn ignores_classes_in_comments_and_strings() {
+        let (css, compiler) = compile(
+            "/* .commented { } */ .a[title=\".quoted\"] { content: \"}\"; } .b { content: '{'; }",
+        );
+        assert_eq!(
+            css,
+            "/* .commented { } */ .a_abc[title=\".quoted\"] { content: \"}\"; } .b_abc { content: '{'; }"
+        );
+        assert_eq!(compiler.classes.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn removes_composes_declarations() {
+        let (css, _) = compile(".a { color: red; composes: b; margin: 0; } .b {}");
+        assert_eq!(css, ".a_abc { color: red; margin: 0; } .b_abc {}");
+    }
+
+    #[test]
+    fn renders_composed_classes() {
+        let (css, compiler) = compile(
+            ".a { composes: b; composes: c from global; } .b { composes: d from \"./other.module.css\"; }",
+        );
+        let js = compiler.render(&css, "src/a.module.css");
+
+        assert!(js.starts_with("import __composes_0 from \"./other.module.css\";\n"));
+        assert!(js.contains(
+            "  \"a\": \"a_abc b_abc c\" + \" \" + __composes_0[\"d\"],\n  \"b\": \"b_abc\" + \" \" + __composes_0[\"d\"],\n"
+        ));
+    }
+
+    #[test]
+    fn parses_composes() {
+        assert!(parse_composes("color: red;").is_none());
+        assert!(parse_composes("composes-other: a;").is_none());
+
+        let composed = parse_composes(" composes: a b from './x.module.css';").unwrap();
+        let names: Vec<_> = composed
+            .iter()
+            .map(|class| match class {
+                ComposedClass::Imported { request, name } => format!("{}#{}", request, name),
+                _ => panic!("Expected an imported class"),
+            })
+            .collect();
+        assert_eq!(names, vec!["./x.module.css#a", "./x.module.css#b"]);
+    }
+}
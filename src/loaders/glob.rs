@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use super::context::find_files;
+use crate::utils::to_js_string;
+
+/// Glob requests, e.g. `./pages/?glob=**/*.tsx&eager`, are created for
+/// `import.meta.glob` calls
+pub fn is_glob_query(query: &str) -> bool {
+    query.starts_with("glob=")
+}
+
+/// Emits a CommonJS module with the namespace of every file in `dir` matching
+/// the glob in `query`, keyed by its path relative to `dir`. Files are
+/// imported when the module runs if the glob is eager, otherwise each is a
+/// function returning a promise of the namespace, loaded on demand.
+pub fn glob_to_js(dir: &Path, query: &str) -> Result<String, String> {
+    let query = &query["glob=".len()..];
+    let (glob, eager) = match query.strip_suffix("&eager") {
+        Some(glob) => (glob, true),
+        None => (query, false),
+    };
+    let dir_name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("Invalid glob directory: {}", dir.to_str().unwrap()))?;
+
+    let mut modules = String::new();
+    for relative_path in find_files(dir)? {
+        if !matches_path_glob(glob, &relative_path) {
+            continue;
+        }
+
+        // Requests are resolved from the glob module's parent directory
+        let request = to_js_string(&format!("./{}/{}", dir_name, relative_path));
+        let value = if eager {
+            format!("require({}, 'namespace')", request)
+        } else {
+            format!(
+                "function () {{ return require.async({}, 'namespace'); }}",
+                request
+            )
+        };
+
+        modules.push_str(&format!("  {}: {},\n", to_js_string(&relative_path), value));
+    }
+
+    Ok(format!("module.exports = {{\n{}}};\n", modules))
+}
+
+/// Matches a `/` separated path against a glob, where `**` matches any number
+/// of directories and `*` matches any characters within a single one, as in
+/// `import.meta.glob` patterns
+fn matches_path_glob(glob: &str, path: &str) -> bool {
+    let glob: Vec<&str> = glob.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    matches_segments(&glob, &path)
+}
+
+fn matches_segments(glob: &[&str], path: &[&str]) -> bool {
+    match (glob.first(), path.first()) {
+        (None, None) => true,
+        (Some(&"**"), _) => {
+            matches_segments(&glob[1..], path)
+                || (!path.is_empty() && matches_segments(glob, &path[1..]))
+        }
+        (Some(pattern), Some(segment)) => {
+            matches_segment(pattern, segment) && matches_segments(&glob[1..], &path[1..])
+        }
+        _ => false,
+    }
+}
+
+fn matches_segment(pattern: &str, segment: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == segment,
+        Some((prefix, rest)) => match segment.strip_prefix(prefix) {
+            Some(remaining) => remaining
+                .char_indices()
+                .map(|(index, _)| index)
+                .chain(Some(remaining.len()))
+                .any(|index| matches_segment(rest, &remaining[index..])),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_stars_within_a_directory() {
+        assert!(matches_path_glob("*.tsx", "index.tsx"));
+        assert!(matches_path_glob("page-*.tsx", "page-about.tsx"));
+        assert!(matches_path_glob("*", "index.tsx"));
+        assert!(!matches_path_glob("*.tsx", "blog/post.tsx"));
+        assert!(!matches_path_glob("*.tsx", "index.ts"));
+        assert!(!matches_path_glob("page-*.tsx", "about.tsx"));
+    }
+
+    #[test]
+    fn matches_globstars_across_directories() {
+        assert!(matches_path_glob("**/*.tsx", "index.tsx"));
+        assert!(matches_path_glob("**/*.tsx", "blog/2021/post.tsx"));
+        assert!(matches_path_glob("blog/**/index.tsx", "blog/index.tsx"));
+        assert!(matches_path_glob("blog/**/index.tsx", "blog/a/b/index.tsx"));
+        assert!(!matches_path_glob("blog/**/index.tsx", "docs/index.tsx"));
+    }
+
+    #[test]
+    fn matches_exact_paths() {
+        assert!(matches_path_glob("blog/post.tsx", "blog/post.tsx"));
+        assert!(!matches_path_glob("blog/post.tsx", "blog/post.tsx.map"));
+        assert!(!matches_path_glob("blog", "blog/post.tsx"));
+    }
+}
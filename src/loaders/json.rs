@@ -0,0 +1,21 @@
+use std::path::Path;
+
+pub fn is_json(filepath: &Path) -> bool {
+    matches!(
+        filepath.extension().and_then(|ext| ext.to_str()),
+        Some("json")
+    )
+}
+
+/// JSON modules are emitted as CommonJS so `require` receives the parsed value,
+/// while runtime interop gives ESM importers the value as the default export
+/// and its top-level keys as named exports.
+pub fn json_to_js(json: &str) -> Result<String, String> {
+    let json = json.trim_start_matches('\u{feff}').trim();
+
+    if json.is_empty() {
+        return Err(String::from("JSON module is empty"));
+    }
+
+    Ok(format!("module.exports = {};\n", json))
+}
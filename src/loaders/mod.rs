@@ -0,0 +1,110 @@
+pub mod asset;
+pub mod context;
+pub mod css_modules;
+pub mod glob;
+pub mod json;
+pub mod worker;
+
+use crate::js_module::Asset;
+use crate::utils::{create_module_id, to_js_string};
+use crate::Config;
+use std::fs;
+use std::path::Path;
+
+pub struct LoadedModule {
+    pub code: String,
+    pub asset: Option<Asset>,
+    /// Whether `code` was generated by a loader rather than read from the file
+    pub is_generated: bool,
+}
+
+/// Whether `query` changes how a file is loaded, see `load_module`. Other
+/// queries like a cache busting `?v=1` are dropped from the module id so the
+/// file is only bundled once.
+pub fn is_loader_query(query: &str) -> bool {
+    matches!(query, "raw" | "url" | "inline") || worker::is_worker_query(query)
+}
+
+/// Reads the file at `filepath` and returns JS source for it, converting
+/// non-JS module types into an equivalent ES module. A `raw`, `url` or
+/// `inline` query overrides how the file is loaded regardless of its type,
+/// a `context` or `glob` query loads the matching files of a directory and a
+/// `worker` query loads the URL of the file's worker chunk.
+pub fn load_module(
+    filepath: &Path,
+    query: Option<&str>,
+    module_id: &str,
+    config: &Config,
+) -> Result<LoadedModule, String> {
+    match query {
+        Some("raw") => {
+            let src_code = read_file(filepath)?;
+            return Ok(LoadedModule {
+                code: format!("export default {};\n", to_js_string(&src_code)),
+                asset: None,
+                is_generated: true,
+            });
+        }
+        Some("url") => {
+            let contents = asset::read_asset(filepath)?;
+            return Ok(asset::emit_asset(filepath, &contents, config));
+        }
+        Some("inline") => {
+            let contents = asset::read_asset(filepath)?;
+            return Ok(asset::inline_asset(filepath, &contents));
+        }
+        Some(query) if context::is_context_query(query) => {
+            return Ok(LoadedModule {
+                code: context::context_to_js(filepath, &query["context=".len()..])?,
+                asset: None,
+                is_generated: true,
+            });
+        }
+        Some(query) if worker::is_worker_query(query) => {
+            let worker_id = create_module_id(filepath, &config.project_root);
+            return Ok(worker::load_worker(&worker_id, config));
+        }
+        Some(query) if glob::is_glob_query(query) => {
+            return Ok(LoadedModule {
+                code: glob::glob_to_js(filepath, query)?,
+                asset: None,
+                is_generated: true,
+            });
+        }
+        _ => {}
+    }
+
+    if asset::is_asset(filepath, config) {
+        return asset::load_asset(filepath, config);
+    }
+
+    let src_code = read_file(filepath)?;
+
+    if css_modules::is_css_module(filepath) {
+        return Ok(LoadedModule {
+            code: css_modules::css_module_to_js(&src_code, module_id),
+            asset: None,
+            is_generated: true,
+        });
+    }
+
+    if json::is_json(filepath) {
+        return Ok(LoadedModule {
+            code: json::json_to_js(&src_code)
+                .map_err(|err| format!("{}: {}", err, filepath.to_str().unwrap()))?,
+            asset: None,
+            is_generated: true,
+        });
+    }
+
+    Ok(LoadedModule {
+        code: src_code,
+        asset: None,
+        is_generated: false,
+    })
+}
+
+fn read_file(filepath: &Path) -> Result<String, String> {
+    fs::read_to_string(filepath)
+        .map_err(|_| format!("Failed to read file: {}", filepath.to_str().unwrap()))
+}
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use super::LoadedModule;
+use crate::utils::{short_hash, to_js_string};
+use crate::Config;
+
+/// Worker requests, e.g. `./parser.worker.ts?worker`, are created for
+/// `new Worker(new URL('./parser.worker.ts', import.meta.url))`
+pub fn is_worker_query(query: &str) -> bool {
+    query == "worker"
+}
+
+/// Default exports the public URL of the worker's chunk. The worker itself is
+/// built as a separate entrypoint, and its chunk is named after its content, so
+/// the URL ends in a placeholder until the chunk is rendered.
+pub fn load_worker(worker_id: &str, config: &Config) -> LoadedModule {
+    let url = format!("{}{}", config.public_path(), worker_placeholder(worker_id));
+
+    LoadedModule {
+        code: format!("export default {};\n", to_js_string(&url)),
+        asset: None,
+        is_generated: true,
+    }
+}
+
+/// Stands in for the filename of a worker's chunk in the chunks creating it
+fn worker_placeholder(worker_id: &str) -> String {
+    format!("__WORKER_{}__", short_hash(worker_id.as_bytes()))
+}
+
+/// Names each worker chunk after a hash of its content, then replaces the
+/// placeholder of its filename in `main_chunks` and the other worker chunks.
+/// Workers are named after any workers they create, so a worker's filename
+/// changes with theirs, except when workers create each other. Returns the
+/// filename and content of each chunk in `worker_chunks`, which are keyed by
+/// the worker's module id.
+pub fn name_worker_chunks(
+    main_chunks: &mut [String],
+    worker_chunks: Vec<(&str, String)>,
+) -> Vec<(String, String)> {
+    let placeholders: Vec<String> = worker_chunks
+        .iter()
+        .map(|(worker_id, _)| worker_placeholder(worker_id))
+        .collect();
+    let mut filenames: Vec<Option<String>> = vec![None; worker_chunks.len()];
+    let (worker_ids, mut chunks): (Vec<&str>, Vec<String>) = worker_chunks.into_iter().unzip();
+
+    while filenames.iter().any(Option::is_none) {
+        let unnamed: Vec<usize> = (0..chunks.len())
+            .filter(|index| filenames[*index].is_none())
+            .collect();
+        let creates_unnamed = |index: usize| {
+            unnamed
+                .iter()
+                .any(|other| *other != index && chunks[index].contains(&placeholders[*other]))
+        };
+        // Workers that create each other can't wait for one another's names
+        let mut ready: Vec<usize> = unnamed
+            .iter()
+            .copied()
+            .filter(|index| !creates_unnamed(*index))
+            .collect();
+        if ready.is_empty() {
+            ready = unnamed;
+        }
+
+        for index in ready {
+            replace_placeholders(&mut chunks[index], &placeholders, &filenames);
+            filenames[index] = Some(worker_filename(worker_ids[index], &chunks[index]));
+        }
+    }
+
+    // Also replaces the placeholders left by workers creating each other
+    for chunk in main_chunks.iter_mut().chain(&mut chunks) {
+        replace_placeholders(chunk, &placeholders, &filenames);
+    }
+    filenames.into_iter().flatten().zip(chunks).collect()
+}
+
+fn replace_placeholders(chunk: &mut String, placeholders: &[String], filenames: &[Option<String>]) {
+    for (placeholder, filename) in placeholders.iter().zip(filenames) {
+        if let Some(filename) = filename {
+            if chunk.contains(placeholder.as_str()) {
+                *chunk = chunk.replace(placeholder.as_str(), filename);
+            }
+        }
+    }
+}
+
+fn worker_filename(worker_id: &str, chunk: &str) -> String {
+    let stem = Path::new(worker_id)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("worker");
+
+    format!("{}.{}.js", stem, short_hash(chunk.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::slice;
+
+    #[test]
+    fn names_workers_after_the_workers_they_create() {
+        let mut main_chunk = format!("new Worker('/{}');", worker_placeholder("a.js"));
+        let create_chunks = |nested: &str| {
+            vec![
+                (
+                    "a.js",
+                    format!("new Worker('/{}');", worker_placeholder("b.js")),
+                ),
+                ("b.js", format!("postMessage('{}');", nested)),
+            ]
+        };
+
+        let chunks = name_worker_chunks(slice::from_mut(&mut main_chunk), create_chunks("b"));
+        let (a_filename, a_chunk) = &chunks[0];
+        let (b_filename, _) = &chunks[1];
+        assert_eq!(main_chunk, format!("new Worker('/{}');", a_filename));
+        assert_eq!(a_chunk, &format!("new Worker('/{}');", b_filename));
+        assert!(a_filename.starts_with("a.") && b_filename.starts_with("b."));
+
+        // Changing the nested worker changes the name of the worker creating it
+        let mut main_chunk = format!("new Worker('/{}');", worker_placeholder("a.js"));
+        let changed =
+            name_worker_chunks(slice::from_mut(&mut main_chunk), create_chunks("changed"));
+        assert_ne!(changed[0].0, chunks[0].0);
+        assert_ne!(changed[1].0, chunks[1].0);
+    }
+
+    #[test]
+    fn names_workers_that_create_each_other() {
+        let chunks = name_worker_chunks(
+            &mut [],
+            vec![
+                (
+                    "a.js",
+                    format!("new Worker('/{}');", worker_placeholder("b.js")),
+                ),
+                (
+                    "b.js",
+                    format!("new Worker('/{}');", worker_placeholder("a.js")),
+                ),
+            ],
+        );
+
+        assert_eq!(chunks[0].1, format!("new Worker('/{}');", chunks[1].0));
+        assert_eq!(chunks[1].1, format!("new Worker('/{}');", chunks[0].0));
+    }
+}
@@ -1,9 +1,13 @@
-use clap::{App, Arg};
-use ducttape::{compile, Config};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use ducttape::{
+    compile, export_graph, is_global_name, watch, why, Config, External, GraphFormat, OutputFormat,
+    Platform,
+};
 use std::env;
 
 fn main() {
     let matches = App::new("duct-tape")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("entrypoint")
                 .help("Sets the entrypoint to bundle")
@@ -16,17 +20,276 @@ fn main() {
                 .default_value("dist")
                 .value_name("output-dir"),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("Sets how the entry's exports are exposed by the chunk")
+                .possible_values(&["esm", "cjs", "umd", "iife"])
+                .default_value("iife")
+                .value_name("format"),
+        )
+        .arg(
+            Arg::with_name("global_name")
+                .long("global-name")
+                .help("Sets the global variable UMD and IIFE chunks assign the entry's exports to")
+                .required_if("format", "umd")
+                .validator(|name| {
+                    if is_global_name(&name) {
+                        Ok(())
+                    } else {
+                        Err(String::from(
+                            "global-name must be an identifier or a dotted path like MyLib.widgets",
+                        ))
+                    }
+                })
+                .value_name("name"),
+        )
+        .arg(
+            Arg::with_name("public_path")
+                .global(true)
+                .long("public-path")
+                .help("Sets the URL prefix that emitted files are served from, by default the output dir's path from the project root, e.g. /dist/")
+                .value_name("url"),
+        )
+        .arg(
+            Arg::with_name("asset_extensions")
+                .global(true)
+                .long("asset-extensions")
+                .help("Sets the comma separated file extensions to emit as assets")
+                .use_delimiter(true)
+                .value_name("extensions"),
+        )
+        .arg(
+            Arg::with_name("asset_inline_limit")
+                .global(true)
+                .long("asset-inline-limit")
+                .help("Inlines assets smaller than this many bytes as data URIs")
+                .default_value("0")
+                .value_name("bytes"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .global(true)
+                .long("mode")
+                .help("Sets the mode used to select .env files and as process.env.NODE_ENV")
+                .default_value("production")
+                .value_name("mode"),
+        )
+        .arg(
+            Arg::with_name("env_prefix")
+                .global(true)
+                .long("env-prefix")
+                .help("Sets the prefix of env variables exposed on import.meta.env")
+                .default_value("DUCTTAPE_")
+                .validator(|prefix| {
+                    // Every variable starts with an empty prefix, secrets included
+                    if prefix.is_empty() {
+                        Err(String::from("env-prefix can't be empty"))
+                    } else {
+                        Ok(())
+                    }
+                })
+                .value_name("prefix"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("Compiles again when a bundled file changes or a file is added to a glob's directory"),
+        )
+        .arg(
+            Arg::with_name("warn_circular")
+                .long("warn-circular")
+                .help("Warns about each circular dependency in the module graph"),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Writes stats.json with the size of each chunk and module to the output dir"),
+        )
+        .arg(
+            Arg::with_name("stats_html")
+                .long("stats-html")
+                .help("Also writes stats.html, a treemap of module sizes"),
+        )
+        .arg(
+            Arg::with_name("platform")
+                .long("platform")
+                .global(true)
+                .help("Sets the environment the bundle runs in")
+                .possible_values(&["node", "browser", "neutral"])
+                .default_value("browser")
+                .value_name("platform"),
+        )
+        .arg(
+            Arg::with_name("node_globals")
+                .long("node-globals")
+                .global(true)
+                .help("Sets the comma separated Node globals shimmed for the browser platform")
+                .use_delimiter(true)
+                .possible_values(&["global", "process", "Buffer", ""])
+                .value_name("globals"),
+        )
+        .arg(
+            Arg::with_name("polyfill")
+                .long("polyfill")
+                .global(true)
+                .help("Resolves a Node built-in to a polyfill package for the browser platform, e.g. events=events-polyfill")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("builtin=package"),
+        )
+        .arg(
+            Arg::with_name("external")
+                .long("external")
+                .global(true)
+                .help("Leaves matching requests unbundled: an exact name, a prefix ending in *, or a /regex/, with an optional =Global to read from")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("pattern"),
+        )
+        .arg(
+            Arg::with_name("define")
+                .global(true)
+                .long("define")
+                .help("Replaces an identifier or member expression with a JS expression, e.g. __DEV__=false")
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("key=value"),
+        )
+        .subcommand(
+            SubCommand::with_name("why")
+                .about("Prints the shortest import chain from the entrypoint to a module")
+                .arg(
+                    Arg::with_name("entrypoint")
+                        .help("Sets the entrypoint to bundle")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("module")
+                        .help("Path or id of the module to explain")
+                        .required(true)
+                        .index(2),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Prints the module graph without writing the bundle")
+                .arg(
+                    Arg::with_name("entrypoint")
+                        .help("Sets the entrypoint to bundle")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Sets the output format")
+                        .possible_values(&["dot", "json"])
+                        .default_value("dot")
+                        .value_name("format"),
+                ),
+        )
         .get_matches();
 
+    match matches.subcommand() {
+        ("why", Some(why_matches)) => {
+            let module = why_matches.value_of("module").expect("Missing module");
+            why(create_config(why_matches), module);
+        }
+        ("graph", Some(graph_matches)) => {
+            let format = match graph_matches.value_of("format") {
+                Some("json") => GraphFormat::Json,
+                _ => GraphFormat::Dot,
+            };
+            export_graph(create_config(graph_matches), format);
+        }
+        _ => {
+            let output_dir = matches.value_of("output_dir").expect("Missing output-dir");
+            let mut config = create_config(&matches);
+            config.output_dir = config.project_root.join(output_dir);
+            config.format = match matches.value_of("format") {
+                Some("esm") => OutputFormat::Esm,
+                Some("cjs") => OutputFormat::Cjs,
+                Some("umd") => OutputFormat::Umd,
+                _ => OutputFormat::Iife,
+            };
+            config.global_name = matches.value_of("global_name").map(String::from);
+            if matches.is_present("watch") {
+                watch(config);
+            } else {
+                compile(config);
+            }
+        }
+    }
+}
+
+/// Creates the config shared by every command from the global args
+fn create_config(matches: &ArgMatches) -> Config {
     let project_root = env::current_dir().expect("Couldn't access CWD");
     let entrypoint = matches.value_of("entrypoint").expect("Missing entrpoint");
-    let output_dir = matches.value_of("output_dir").expect("Missing output-dir");
+    let mode = matches.value_of("mode").expect("Missing mode");
+    let env_prefix = matches.value_of("env_prefix").expect("Missing env-prefix");
+    let asset_inline_limit = matches
+        .value_of("asset_inline_limit")
+        .expect("Missing asset-inline-limit")
+        .parse()
+        .expect("asset-inline-limit must be a number");
 
-    let config = Config {
+    let mut config = Config {
         entrypoint: project_root.join(entrypoint),
-        output_dir: project_root.join(output_dir),
         project_root,
+        public_path: matches.value_of("public_path").map(String::from),
+        asset_inline_limit,
+        mode: mode.to_string(),
+        env_prefix: env_prefix.to_string(),
+        platform: match matches.value_of("platform") {
+            Some("node") => Platform::Node,
+            Some("neutral") => Platform::Neutral,
+            _ => Platform::Browser,
+        },
+        warn_circular: matches.is_present("warn_circular"),
+        stats: matches.is_present("stats"),
+        stats_html: matches.is_present("stats_html"),
+        ..Default::default()
     };
 
-    compile(config);
+    if let Some(asset_extensions) = matches.values_of("asset_extensions") {
+        config.asset_extensions = asset_extensions.map(|ext| ext.to_string()).collect();
+    }
+
+    if let Some(externals) = matches.values_of("external") {
+        config.externals = externals
+            .map(|external| External::parse(external).unwrap_or_else(|err| panic!("{}", err)))
+            .collect();
+    }
+
+    if let Some(node_globals) = matches.values_of("node_globals") {
+        config.node_globals = node_globals
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .collect();
+    }
+
+    if let Some(polyfills) = matches.values_of("polyfill") {
+        for polyfill in polyfills {
+            let (builtin, package) = polyfill
+                .split_once('=')
+                .expect("polyfill must be in the form builtin=package");
+            config
+                .polyfills
+                .insert(builtin.to_string(), package.to_string());
+        }
+    }
+
+    if let Some(defines) = matches.values_of("define") {
+        for define in defines {
+            let (key, value) = define
+                .split_once('=')
+                .expect("define must be in the form key=value");
+            config.define.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    config
 }
@@ -1,15 +1,33 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fmt;
 
-use crate::js_module::JsModule;
+use crate::js_module::{Dependency, ImportType, JsModule};
+use swc_atoms::JsWord;
 
 type ModuleId = String;
 
 pub struct ModuleGraph {
     pub modules: HashMap<ModuleId, JsModule>,
     pub entrypoints: Vec<ModuleId>,
-    pub dependency_map: HashMap<ModuleId, Vec<ModuleId>>,
+    pub dependency_map: HashMap<ModuleId, Vec<DependencyEdge>>,
+    /// Reverse of `dependency_map`, the modules importing each module
+    pub importer_map: HashMap<ModuleId, Vec<ModuleId>>,
+}
+
+/// An edge from an importer to `module_id`. Every import of the same module
+/// within an importer is merged into a single edge.
+pub struct DependencyEdge {
+    pub module_id: ModuleId,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl DependencyEdge {
+    /// An edge is only async if the module is never imported synchronously
+    pub fn is_async(&self) -> bool {
+        self.dependencies.iter().all(|dep| dep.is_async)
+    }
 }
 
 impl ModuleGraph {
@@ -17,6 +35,7 @@ impl ModuleGraph {
         Self {
             modules: HashMap::new(),
             dependency_map: HashMap::new(),
+            importer_map: HashMap::new(),
             entrypoints: Vec::new(),
         }
     }
@@ -33,40 +52,241 @@ impl ModuleGraph {
         self.modules.get_mut(module_id)
     }
 
-    pub fn add_dependency(&mut self, id: &str, dep_id: &str) {
-        match self.dependency_map.get_mut(id) {
-            Some(deps) => {
-                deps.push(dep_id.to_string());
-            }
+    pub fn add_dependency(&mut self, id: &str, dep_id: &str, dependency: Dependency) {
+        let edges = self.dependency_map.entry(id.to_string()).or_default();
+
+        match edges.iter_mut().find(|edge| edge.module_id == dep_id) {
+            Some(edge) => edge.dependencies.push(dependency),
             None => {
-                let deps = vec![dep_id.to_string()];
-                self.dependency_map.insert(id.to_string(), deps);
+                edges.push(DependencyEdge {
+                    module_id: dep_id.to_string(),
+                    dependencies: vec![dependency],
+                });
+                self.importer_map
+                    .entry(dep_id.to_string())
+                    .or_default()
+                    .push(id.to_string());
             }
-        };
+        }
+    }
+
+    pub fn get_dependencies(&self, module_id: &str) -> &[DependencyEdge] {
+        self.dependency_map
+            .get(module_id)
+            .map_or(&[], |edges| &edges[..])
+    }
+
+    pub fn get_importers(&self, module_id: &str) -> &[ModuleId] {
+        self.importer_map
+            .get(module_id)
+            .map_or(&[], |importers| &importers[..])
+    }
+
+    pub fn get_dependency_edge(&self, module_id: &str, dep_id: &str) -> Option<&DependencyEdge> {
+        self.get_dependencies(module_id)
+            .iter()
+            .find(|edge| edge.module_id == dep_id)
     }
 
     pub fn add_entrypoint(&mut self, id: String) {
         self.entrypoints.push(id.to_string());
     }
 
-    pub fn get_module_deps(&self, module_id: &str) -> HashSet<&String> {
-        let mut module_deps = HashSet::new();
+    /// Returns `entry_id` and every module reachable from it in dependency
+    /// post-order, i.e. each module comes after its dependencies and the entry
+    /// comes last. The order only depends on the order of imports within each
+    /// module, so the output is reproducible between builds.
+    pub fn get_modules_in_order<'a>(&'a self, entry_id: &'a String) -> Vec<&'a String> {
+        self.collect_modules_in_order(entry_id, true)
+    }
+
+    /// Like `get_modules_in_order`, but only follows dependencies that are
+    /// loaded before the importer runs, so async imports and their own
+    /// dependencies are left out
+    pub fn get_sync_modules_in_order<'a>(&'a self, entry_id: &'a String) -> Vec<&'a String> {
+        self.collect_modules_in_order(entry_id, false)
+    }
+
+    fn collect_modules_in_order<'a>(
+        &'a self,
+        entry_id: &'a String,
+        follow_async: bool,
+    ) -> Vec<&'a String> {
+        let mut modules: Vec<&String> = vec![];
+        let mut visited: HashSet<&String> = HashSet::new();
+        // Iterative so that import cycles and deep graphs can't overflow the stack
+        let mut stack: Vec<(&String, usize)> = vec![(entry_id, 0)];
+        visited.insert(entry_id);
+
+        while let Some(&(module_id, index)) = stack.last() {
+            match self.dependency_map.get(module_id) {
+                Some(deps) if index < deps.len() => {
+                    stack.last_mut().unwrap().1 += 1;
+                    let dep = &deps[index].module_id;
+                    if (follow_async || !deps[index].is_async()) && visited.insert(dep) {
+                        stack.push((dep, 0));
+                    }
+                }
+                _ => {
+                    stack.pop();
+                    modules.push(module_id);
+                }
+            }
+        }
+
+        modules
+    }
+
+    /// Returns the modules imported by `module_id`'s import declarations, in
+    /// the order they're imported. Unlike requires, these are evaluated before
+    /// the importer runs.
+    pub fn get_static_imports<'a>(&'a self, module_id: &str) -> impl Iterator<Item = &'a String> {
+        self.get_dependencies(module_id)
+            .iter()
+            .filter(|edge| {
+                edge.dependencies
+                    .iter()
+                    .any(|dep| !dep.is_async && !matches!(dep.import_type, ImportType::Require))
+            })
+            .map(|edge| &edge.module_id)
+    }
+
+    /// Returns every module reachable from `entry_id` that is evaluated
+    /// asynchronously, because it uses top-level await or imports a module
+    /// that is.
+    pub fn get_async_modules<'a>(&'a self, entry_id: &'a String) -> HashSet<&'a String> {
+        let modules = self.get_modules_in_order(entry_id);
+        let mut async_modules: HashSet<&String> = modules
+            .iter()
+            .filter(|module_id| self.modules[**module_id].has_top_level_await)
+            .cloned()
+            .collect();
 
-        match self.dependency_map.get(module_id) {
-            Some(deps) => {
-                for dep in deps {
-                    module_deps.extend(&self.get_module_deps(dep));
-                    module_deps.insert(dep);
+        // Post-order already puts dependencies first, except across the back
+        // edge of a cycle, so repeat until nothing changes
+        loop {
+            let mut changed = false;
+            for module_id in &modules {
+                if !async_modules.contains(module_id)
+                    && self
+                        .get_static_imports(module_id)
+                        .any(|dep| async_modules.contains(dep))
+                {
+                    async_modules.insert(module_id);
+                    changed = true;
                 }
             }
-            None => {}
+            if !changed {
+                break;
+            }
         }
 
-        module_deps
+        async_modules
+    }
+
+    /// Returns the names exported by an ES module, following `export * from`
+    /// re-exports.
+    pub fn get_export_names(&self, module_id: &str) -> Vec<&JsWord> {
+        let mut names: Vec<&JsWord> = vec![];
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<(&str, bool)> = vec![(module_id, false)];
+
+        while let Some((id, is_star_export)) = stack.pop() {
+            let module = match self.modules.get(id) {
+                Some(module) if visited.insert(id) => module,
+                _ => continue,
+            };
+
+            for name in &module.exports.names {
+                // Star exports never re-export a default
+                let is_star_default = is_star_export && &**name == "default";
+                if !is_star_default && !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+
+            for request in module.exports.star_requests.iter().rev() {
+                let edge = self.get_dependencies(id).iter().find(|edge| {
+                    edge.dependencies
+                        .iter()
+                        .any(|dependency| &dependency.request == request)
+                });
+                if let Some(edge) = edge {
+                    stack.push((&edge.module_id, true));
+                }
+            }
+        }
+
+        names
+    }
+
+    /// Finds the shortest import chain from each entrypoint to `module_id`.
+    /// Each chain starts with the entrypoint and ends with `module_id`. Only one
+    /// chain per entrypoint is returned as there can be exponentially many.
+    pub fn find_import_chains<'a>(&'a self, module_id: &'a String) -> Vec<Vec<&'a String>> {
+        // Breadth-first from `module_id` back towards the entrypoints, mapping
+        // each importer to the module it imports on its way to `module_id`
+        let mut next_hops: HashMap<&String, &String> = HashMap::new();
+        let mut queue: VecDeque<&String> = VecDeque::new();
+        queue.push_back(module_id);
+
+        while let Some(id) = queue.pop_front() {
+            for importer in self.get_importers(id) {
+                if importer != module_id && !next_hops.contains_key(importer) {
+                    next_hops.insert(importer, id);
+                    queue.push_back(importer);
+                }
+            }
+        }
+
+        self.entrypoints
+            .iter()
+            .filter(|entry_id| *entry_id == module_id || next_hops.contains_key(entry_id))
+            .map(|entry_id| {
+                let mut chain = vec![entry_id];
+                while let Some(next_hop) = next_hops.get(chain[chain.len() - 1]) {
+                    chain.push(next_hop);
+                }
+                chain
+            })
+            .collect()
+    }
+
+    /// Finds every import cycle reachable from `entry_id`. Each cycle starts and
+    /// ends with the same module, e.g. `[a, b, a]`.
+    pub fn find_cycles<'a>(&'a self, entry_id: &'a String) -> Vec<Vec<&'a String>> {
+        let mut cycles: Vec<Vec<&String>> = vec![];
+        let mut visited: HashSet<&String> = HashSet::new();
+        // The current import chain, with the index of the next dependency to visit
+        let mut stack: Vec<(&String, usize)> = vec![(entry_id, 0)];
+        visited.insert(entry_id);
+
+        while let Some(&(module_id, index)) = stack.last() {
+            let dep = match self.dependency_map.get(module_id) {
+                Some(deps) if index < deps.len() => &deps[index].module_id,
+                _ => {
+                    stack.pop();
+                    continue;
+                }
+            };
+            stack.last_mut().unwrap().1 += 1;
+
+            if let Some(start) = stack.iter().position(|(id, _)| *id == dep) {
+                let mut cycle: Vec<&String> = stack[start..].iter().map(|(id, _)| *id).collect();
+                cycle.push(dep);
+                if !cycles.contains(&cycle) {
+                    cycles.push(cycle);
+                }
+            } else if visited.insert(dep) {
+                stack.push((dep, 0));
+            }
+        }
+
+        cycles
     }
 }
 
-impl<'a> fmt::Debug for ModuleGraph {
+impl fmt::Debug for ModuleGraph {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut modules: String = String::new();
         for (module_id, module) in &self.modules {
@@ -76,3 +296,184 @@ impl<'a> fmt::Debug for ModuleGraph {
         write!(f, "{}", modules)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::js_module::{ImportType, ModuleExports, ModuleType};
+    use std::path::PathBuf;
+    use swc_common::DUMMY_SP;
+
+    fn create_dependency(is_async: bool) -> Dependency {
+        Dependency {
+            request: "./dep".into(),
+            import_type: ImportType::Require,
+            span: DUMMY_SP,
+            is_async,
+            loc: None,
+        }
+    }
+
+    fn create_graph(edges: &[(&str, &str)]) -> ModuleGraph {
+        let mut graph = ModuleGraph::new();
+        for (id, dep_id) in edges {
+            graph.add_dependency(id, dep_id, create_dependency(false));
+        }
+        graph
+    }
+
+    #[test]
+    fn finds_cycles_reachable_from_the_entry() {
+        let graph = create_graph(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("c", "d"),
+            ("d", "d"),
+            ("e", "e"),
+        ]);
+        let entry_id = String::from("a");
+
+        assert_eq!(
+            graph.find_cycles(&entry_id),
+            vec![vec!["a", "b", "c", "a"], vec!["d", "d"]]
+        );
+    }
+
+    #[test]
+    fn orders_dependencies_before_importers() {
+        let graph = create_graph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+        let entry_id = String::from("a");
+
+        assert_eq!(
+            graph.get_modules_in_order(&entry_id),
+            vec!["d", "b", "c", "a"]
+        );
+    }
+
+    #[test]
+    fn orders_circular_modules_once() {
+        let graph = create_graph(&[("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")]);
+        let entry_id = String::from("a");
+
+        assert_eq!(
+            graph.get_modules_in_order(&entry_id),
+            vec!["d", "c", "b", "a"]
+        );
+    }
+
+    #[test]
+    fn merges_imports_of_the_same_module_into_one_edge() {
+        let mut graph = ModuleGraph::new();
+        graph.add_dependency("a", "b", create_dependency(true));
+        graph.add_dependency("a", "b", create_dependency(false));
+        graph.add_dependency("a", "c", create_dependency(true));
+        graph.add_dependency("d", "b", create_dependency(false));
+
+        let edges = graph.get_dependencies("a");
+        assert_eq!(edges.len(), 2);
+        assert!(!edges[0].is_async());
+        assert!(edges[1].is_async());
+        assert_eq!(graph.get_importers("b"), ["a", "d"]);
+        assert!(graph.get_dependencies("b").is_empty());
+    }
+
+    #[test]
+    fn finds_the_shortest_import_chain_from_each_entrypoint() {
+        let mut graph = create_graph(&[
+            ("main", "a"),
+            ("a", "b"),
+            ("b", "target"),
+            ("main", "c"),
+            ("c", "target"),
+            ("target", "a"),
+            ("admin", "b"),
+        ]);
+        graph.add_entrypoint(String::from("main"));
+        graph.add_entrypoint(String::from("admin"));
+        graph.add_entrypoint(String::from("unrelated"));
+        let target = String::from("target");
+
+        assert_eq!(
+            graph.find_import_chains(&target),
+            vec![vec!["main", "c", "target"], vec!["admin", "b", "target"]]
+        );
+    }
+
+    #[test]
+    fn collects_export_names_through_star_exports() {
+        let mut graph = ModuleGraph::new();
+        for (id, names, star_request) in &[
+            ("a", vec!["a", "default"], "./b"),
+            ("b", vec!["b", "default"], "./c"),
+            ("c", vec!["c", "a"], "./a"),
+        ] {
+            graph.add_module(JsModule {
+                id: id.to_string(),
+                filepath: PathBuf::from(id),
+                code: String::new(),
+                original_size: 0,
+                module_type: ModuleType::ESM,
+                asset: None,
+                exports: ModuleExports {
+                    names: names.iter().map(|&name| name.into()).collect(),
+                    star_requests: vec![(*star_request).into()],
+                },
+                shims: vec![],
+                has_top_level_await: false,
+            });
+        }
+        for (id, dep_id) in &[("a", "b"), ("b", "c"), ("c", "a")] {
+            let mut dependency = create_dependency(false);
+            dependency.request = format!("./{}", dep_id).into();
+            graph.add_dependency(id, dep_id, dependency);
+        }
+
+        assert_eq!(graph.get_export_names("a"), vec!["a", "default", "b", "c"]);
+    }
+
+    #[test]
+    fn marks_importers_of_async_modules_as_async() {
+        let mut graph = ModuleGraph::new();
+        for (id, has_top_level_await) in &[
+            ("main", false),
+            ("a", false),
+            ("b", false),
+            ("tla", true),
+            ("required", false),
+        ] {
+            graph.add_module(JsModule {
+                id: id.to_string(),
+                filepath: PathBuf::from(id),
+                code: String::new(),
+                original_size: 0,
+                module_type: ModuleType::ESM,
+                asset: None,
+                exports: ModuleExports::default(),
+                shims: vec![],
+                has_top_level_await: *has_top_level_await,
+            });
+        }
+        // `b` imports `a` back before `a` is known to be async through `tla`
+        for (id, dep_id) in &[
+            ("main", "a"),
+            ("a", "b"),
+            ("b", "a"),
+            ("a", "tla"),
+            ("main", "required"),
+            ("required", "tla"),
+        ] {
+            let mut dependency = create_dependency(false);
+            if *id != "required" {
+                dependency.import_type = ImportType::SideEffect;
+            }
+            graph.add_dependency(id, dep_id, dependency);
+        }
+        let entry_id = String::from("main");
+
+        let mut async_modules: Vec<&String> =
+            graph.get_async_modules(&entry_id).into_iter().collect();
+        async_modules.sort();
+        assert_eq!(async_modules, vec!["a", "b", "main", "tla"]);
+    }
+}
@@ -1,55 +1,94 @@
-use crate::diagnostics::{Diagnostic, ModuleBuildSuccess};
-use crate::js_module::ModuleType;
-use crate::js_module::{Dependency, JsModule};
+use crate::diagnostics::{
+    Diagnostic, DynamicRequire, InvalidGlob, MissingPolyfill, ModuleBuildSuccess, NodePolyfill,
+    NodeShim,
+};
+use crate::env::import_meta_env_defines;
+use crate::externals::{find_polyfill, is_external, is_node_builtin};
+use crate::js_module::{Asset, Dependency, JsModule, Location};
+use crate::js_module::{ModuleExports, ModuleType};
+use crate::loaders::context::is_context_query;
+use crate::loaders::glob::is_glob_query;
+use crate::loaders::worker::is_worker_query;
+use crate::loaders::{is_loader_query, load_module};
 use crate::parser::parse;
+use crate::transforms::define::{define, parse_defines, Defines};
+use crate::transforms::node_shims::node_shims;
 use crate::transforms::runtime_imports::runtime_imports;
-use crate::utils::create_module_id;
-use crate::Compilation;
+use crate::transforms::top_level_await::has_top_level_await;
+use crate::utils::{
+    create_module_id, create_module_id_with_query, normalize_path, split_query, to_js_string,
+};
+use crate::{Compilation, Config, OutputFormat, Platform};
 use node_resolve::Resolver;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::time::Duration;
-use swc_atoms::JsWord;
 
 use ast::*;
 use crossbeam_channel::unbounded;
 use rayon::ThreadPoolBuilder;
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::thread;
 use std::time::Instant;
 use swc_common::chain;
 use swc_common::comments::SingleThreadedComments;
 use swc_common::sync::Lrc;
-use swc_common::SourceMap;
+use swc_common::{Globals, Mark, SourceMap, Span, GLOBALS};
 use swc_ecmascript::ast;
 use swc_ecmascript::codegen::text_writer::JsWriter;
-use swc_ecmascript::transforms::{react, typescript};
+use swc_ecmascript::transforms::optimization::simplify::{dead_branch_remover, expr_simplifier};
+use swc_ecmascript::transforms::resolver::ts_resolver;
+use swc_ecmascript::transforms::{fixer, react, typescript};
 use swc_ecmascript::visit::FoldWith;
 
+/// Extensions tried, in order, when a request has none
+pub const RESOLVE_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "mjs", "json"];
+
+struct BuildModule {
+    filepath: PathBuf,
+    query: Option<String>,
+}
+
 struct BuildModuleSuccess {
+    module_id: String,
     filepath: PathBuf,
     code: String,
+    original_size: u64,
+    asset: Option<Asset>,
     module_type: ModuleType,
+    exports: ModuleExports,
     dependencies: Vec<Dependency>,
+    shims: Vec<String>,
+    has_top_level_await: bool,
+    dynamic_requires: Vec<Location>,
+    invalid_globs: Vec<(Location, String)>,
     duration: Duration,
 }
 
 struct ResolveModule {
     source_filepath: PathBuf,
-    request: JsWord,
+    dependency: Dependency,
     parent_module_id: String,
+    /// Position of the dependency within its parent's imports
+    index: usize,
+    /// Package resolved instead of a Node built-in for the browser
+    polyfill: Option<String>,
 }
 
 struct ResolveModuleSuccess {
     filepath: PathBuf,
+    query: Option<String>,
     dep_id: String,
     parent_module_id: String,
-    request: JsWord,
+    dependency: Dependency,
+    index: usize,
+    polyfill: Option<String>,
 }
 
 enum WorkMsg {
     ResolveModule(ResolveModule),
-    BuildModule(PathBuf),
+    BuildModule(BuildModule),
     Exit,
 }
 
@@ -66,14 +105,29 @@ pub fn load_entrypoint(c: &mut Compilation) {
         .build()
         .expect("Failed to create ThreadPool");
     let project_root = c.config.project_root.clone();
+    let config = Arc::new(c.config.clone());
+    let mut define = import_meta_env_defines(&c.config);
+    // NODE_ENV follows the mode unless it's defined explicitly
+    define.insert(
+        String::from("process.env.NODE_ENV"),
+        to_js_string(&c.config.mode),
+    );
+    define.extend(c.config.define.clone());
+    let defines = Arc::new(parse_defines(&define).expect("Failed to parse define"));
 
     thread::spawn(move || loop {
         match work_receiver.recv() {
-            Ok(WorkMsg::BuildModule(filepath)) => {
+            Ok(WorkMsg::BuildModule(work)) => {
                 let result_sender = result_sender.clone();
+                let config = config.clone();
+                let defines = defines.clone();
 
                 pool.install(move || {
-                    let result = build_module(filepath).expect("Failed to build module");
+                    // The scope resolver and some swc transforms (e.g. TS namespaces)
+                    // create hygiene marks
+                    let result = GLOBALS
+                        .set(&Globals::new(), || build_module(work, &config, &defines))
+                        .expect("Failed to build module");
 
                     result_sender
                         .send(ResultMsg::BuildModule(result))
@@ -85,15 +139,24 @@ pub fn load_entrypoint(c: &mut Compilation) {
                 let project_root = project_root.clone();
 
                 pool.install(move || {
-                    let resolved_filepath = resolve_module(work.source_filepath, &work.request[..]);
-                    let dep_id = create_module_id(&resolved_filepath, &project_root);
+                    let polyfill = work.polyfill;
+                    let request = polyfill.as_deref().unwrap_or(&work.dependency.request);
+                    let (resolved_filepath, query) = resolve_module(work.source_filepath, request);
+                    let dep_id = create_module_id_with_query(
+                        &resolved_filepath,
+                        &project_root,
+                        query.as_deref(),
+                    );
 
                     result_sender
                         .send(ResultMsg::ResolveModule(ResolveModuleSuccess {
                             filepath: resolved_filepath,
+                            query,
                             dep_id,
                             parent_module_id: work.parent_module_id,
-                            request: work.request,
+                            dependency: work.dependency,
+                            index: work.index,
+                            polyfill,
                         }))
                         .expect("Failed to send ResolveModule result from thread");
                 });
@@ -108,16 +171,27 @@ pub fn load_entrypoint(c: &mut Compilation) {
 
     // Trigger initial build by add entrypoint to work queue
     work_sender
-        .send(WorkMsg::BuildModule(c.config.entrypoint.clone()))
+        .send(WorkMsg::BuildModule(BuildModule {
+            filepath: c.config.entrypoint.clone(),
+            query: None,
+        }))
         .unwrap();
 
     let mut active_work_count = 1;
     let mut found_modules: HashSet<String> = HashSet::new();
+    found_modules.insert(create_module_id_with_query(
+        &c.config.entrypoint,
+        &c.config.project_root,
+        None,
+    ));
+    // Dependencies resolve in parallel, so they are only added to the graph once
+    // everything is loaded, in the order they're imported, to keep output stable
+    let mut resolved_deps: HashMap<String, Vec<(usize, String, Dependency)>> = HashMap::new();
 
     loop {
         match result_receiver.recv() {
             Ok(ResultMsg::BuildModule(result)) => {
-                let module_id = create_module_id(&result.filepath, &c.config.project_root.clone());
+                let module_id = result.module_id;
                 c.diagnostics
                     .add_diagnostic(Diagnostic::ModuleBuildSuccess(ModuleBuildSuccess {
                         module_id: module_id.clone(),
@@ -126,13 +200,68 @@ pub fn load_entrypoint(c: &mut Compilation) {
 
                 active_work_count -= 1;
 
-                for dep in result.dependencies {
+                for shim in &result.shims {
+                    c.diagnostics.add_diagnostic(Diagnostic::NodeShim(NodeShim {
+                        module_id: module_id.clone(),
+                        name: shim.clone(),
+                    }));
+                }
+
+                for loc in result.dynamic_requires {
+                    c.diagnostics
+                        .add_diagnostic(Diagnostic::DynamicRequire(DynamicRequire {
+                            module_id: module_id.clone(),
+                            loc: Some(loc),
+                        }));
+                }
+
+                for (loc, message) in result.invalid_globs {
+                    c.diagnostics
+                        .add_diagnostic(Diagnostic::InvalidGlob(InvalidGlob {
+                            module_id: module_id.clone(),
+                            loc: Some(loc),
+                            message,
+                        }));
+                }
+
+                for (index, dependency) in result.dependencies.into_iter().enumerate() {
+                    // Externals are loaded at runtime so are never resolved or added to the graph
+                    if is_external(&c.config, &dependency.request) {
+                        c.external_requests
+                            .entry(module_id.clone())
+                            .or_default()
+                            .insert(dependency.request.to_string());
+                        continue;
+                    }
+
+                    // Browser builds swap Node built-ins for a polyfill package, or
+                    // leave them external when there isn't one to bundle
+                    let polyfill = find_polyfill(&c.config, &dependency.request, &result.filepath);
+                    if polyfill.is_none()
+                        && c.config.platform == Platform::Browser
+                        && is_node_builtin(&dependency.request)
+                    {
+                        c.diagnostics.add_diagnostic(Diagnostic::MissingPolyfill(
+                            MissingPolyfill {
+                                module_id: module_id.clone(),
+                                request: dependency.request.to_string(),
+                            },
+                        ));
+                        c.external_requests
+                            .entry(module_id.clone())
+                            .or_default()
+                            .insert(dependency.request.to_string());
+                        continue;
+                    }
+
                     active_work_count += 1;
                     work_sender
                         .send(WorkMsg::ResolveModule(ResolveModule {
-                            request: dep.request.clone(),
+                            dependency,
                             parent_module_id: module_id.clone(),
                             source_filepath: result.filepath.clone(),
+                            index,
+                            polyfill,
                         }))
                         .expect("Failed to send ResolveModule reqest");
                 }
@@ -141,7 +270,12 @@ pub fn load_entrypoint(c: &mut Compilation) {
                     id: module_id,
                     filepath: result.filepath,
                     code: result.code,
+                    original_size: result.original_size,
                     module_type: result.module_type,
+                    asset: result.asset,
+                    exports: result.exports,
+                    shims: result.shims,
+                    has_top_level_await: result.has_top_level_await,
                 });
 
                 if active_work_count == 0 {
@@ -149,22 +283,59 @@ pub fn load_entrypoint(c: &mut Compilation) {
                 }
             }
             Ok(ResultMsg::ResolveModule(result)) => {
+                if let Some(polyfill) = result.polyfill {
+                    c.diagnostics
+                        .add_diagnostic(Diagnostic::NodePolyfill(NodePolyfill {
+                            module_id: result.parent_module_id.clone(),
+                            request: result.dependency.request.to_string(),
+                            polyfill,
+                        }));
+                }
+
                 let graph = &mut c.graph;
 
                 graph
                     .get_module(&result.parent_module_id)
                     .expect("Failed to get requesting module")
-                    .update_dep_src(&result.request, &result.dep_id);
+                    .update_dep_src(&result.dependency.request, &result.dep_id);
 
-                graph.add_dependency(&result.parent_module_id, &result.dep_id);
+                resolved_deps
+                    .entry(result.parent_module_id)
+                    .or_default()
+                    .push((result.index, result.dep_id.clone(), result.dependency));
+
+                // Workers are bundled as a separate entrypoint with their own chunk
+                if result.query.as_deref().is_some_and(is_worker_query) {
+                    let worker_id = create_module_id(&result.filepath, &c.config.project_root);
+                    if !c.graph.entrypoints.contains(&worker_id) {
+                        c.graph.add_entrypoint(worker_id.clone());
+                    }
+                    if found_modules.insert(worker_id) {
+                        active_work_count += 1;
+                        work_sender
+                            .send(WorkMsg::BuildModule(BuildModule {
+                                filepath: result.filepath.clone(),
+                                query: None,
+                            }))
+                            .expect("Failed to send BuildModule request");
+                    }
+                }
 
                 if !found_modules.contains(&result.dep_id) {
                     found_modules.insert(result.dep_id.clone());
                     work_sender
-                        .send(WorkMsg::BuildModule(result.filepath))
+                        .send(WorkMsg::BuildModule(BuildModule {
+                            filepath: result.filepath,
+                            query: result.query,
+                        }))
                         .expect("Failed to send BuildModule request");
                 } else {
+                    // Already built or building, e.g. the back edge of a cycle
                     active_work_count -= 1;
+
+                    if active_work_count == 0 {
+                        work_sender.send(WorkMsg::Exit).unwrap();
+                    }
                 }
             }
             Ok(ResultMsg::Exited) => {
@@ -173,22 +344,86 @@ pub fn load_entrypoint(c: &mut Compilation) {
             _ => panic!("Error receiving a ResultMsg."),
         }
     }
+
+    // Workers are found in parallel, so are sorted to keep output stable
+    c.graph.entrypoints[1..].sort();
+
+    // Importers are added in a fixed order too, as they're listed by importer
+    let mut resolved_deps: Vec<_> = resolved_deps.into_iter().collect();
+    resolved_deps.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (module_id, mut deps) in resolved_deps {
+        deps.sort_by_key(|(index, _, _)| *index);
+        for (_, dep_id, dependency) in deps {
+            c.graph.add_dependency(&module_id, &dep_id, dependency);
+        }
+    }
 }
 
-fn build_module(filepath: PathBuf) -> Result<BuildModuleSuccess, String> {
+fn build_module(
+    work: BuildModule,
+    config: &Config,
+    defines: &Defines,
+) -> Result<BuildModuleSuccess, String> {
     let start = Instant::now();
     let source_map = Lrc::new(SourceMap::default());
 
-    let src_code = fs::read_to_string(&filepath).expect(&format!(
-        "Failed to read file: {}",
-        &filepath.to_str().unwrap()
-    ));
-    let (module, comments) = match parse(&src_code, &filepath.to_str().unwrap(), &source_map) {
+    let filepath = work.filepath;
+    let module_id =
+        create_module_id_with_query(&filepath, &config.project_root, work.query.as_deref());
+    let loaded = load_module(&filepath, work.query.as_deref(), &module_id, config)?;
+    // Context modules are directories, so have no size of their own
+    let original_size = fs::metadata(&filepath)
+        .ok()
+        .filter(|metadata| metadata.is_file())
+        .map_or(0, |metadata| metadata.len());
+    let (module, comments) = match parse(&loaded.code, filepath.to_str().unwrap(), &source_map) {
         Err(_) => return Err(String::from("Error parsing module")),
         Ok(module) => module,
     };
 
-    let (module, dependencies, module_type) = runtime_imports(module);
+    // Dead branches are removed before collecting dependencies so that
+    // e.g. development-only requires are never bundled
+    let module = {
+        let mut passes = chain!(define(defines), expr_simplifier(), dead_branch_remover());
+        module.fold_with(&mut passes)
+    };
+
+    // Shims are added before collecting dependencies as they may require a polyfill
+    let (module, shims) = match config.platform {
+        // Buffer is only shimmed when its polyfill package is installed
+        Platform::Browser => node_shims(module, &config.node_globals, |name| {
+            name != "Buffer" || find_polyfill(config, "buffer", &filepath).is_some()
+        }),
+        _ => (module, vec![]),
+    };
+
+    // Scopes are resolved so imported bindings can be told apart from locals
+    // shadowing them when their uses are rewritten
+    let module = module.fold_with(&mut ts_resolver(Mark::fresh(Mark::root())));
+
+    let has_top_level_await = has_top_level_await(&module);
+    let (module, mut dependencies, module_type, exports, dynamic_requires, invalid_globs) =
+        runtime_imports(module, config.format == OutputFormat::Esm);
+
+    let lookup_loc = |span: Span| {
+        let loc = source_map.lookup_char_pos(span.lo);
+        Location {
+            line: loc.line,
+            column: loc.col.0,
+        }
+    };
+    for dependency in &mut dependencies {
+        // Locations in code generated by a loader don't exist in the file
+        if !dependency.span.is_dummy() && !loaded.is_generated {
+            dependency.loc = Some(lookup_loc(dependency.span));
+        }
+    }
+    let dynamic_requires = dynamic_requires.into_iter().map(lookup_loc).collect();
+    let invalid_globs = invalid_globs
+        .into_iter()
+        .map(|(span, message)| (lookup_loc(span), message))
+        .collect();
 
     let final_ast = {
         let react_transform = react::react(
@@ -196,7 +431,11 @@ fn build_module(filepath: PathBuf) -> Result<BuildModuleSuccess, String> {
             Some(&comments),
             react::Options::default(),
         );
-        let mut passes = chain!(typescript::strip(), react_transform);
+        let mut passes = chain!(
+            typescript::strip(),
+            react_transform,
+            fixer::fixer(Some(&comments))
+        );
         module.fold_with(&mut passes)
     };
 
@@ -221,15 +460,23 @@ fn build_module(filepath: PathBuf) -> Result<BuildModuleSuccess, String> {
     };
 
     Ok(BuildModuleSuccess {
+        module_id,
         filepath,
         code,
+        original_size,
+        asset: loaded.asset,
         module_type,
+        exports,
         dependencies,
+        shims,
+        has_top_level_await,
+        dynamic_requires,
+        invalid_globs,
         duration: start.elapsed(),
     })
 }
 
-fn emit(
+pub(crate) fn emit(
     ast: &Module,
     source_map: Lrc<SourceMap>,
     comments: SingleThreadedComments,
@@ -246,16 +493,35 @@ fn emit(
         };
         emitter.emit_module(ast)?;
     }
-    return Ok(buf);
+    Ok(buf)
+}
+
+/// Whether `request` resolves to a file from the module at `source_filepath`
+pub fn can_resolve(source_filepath: &Path, request: &str) -> bool {
+    create_resolver(source_filepath).resolve(request).is_ok()
 }
 
-fn resolve_module(source_filepath: PathBuf, request: &str) -> PathBuf {
+fn create_resolver(source_filepath: &Path) -> Resolver {
     Resolver::new()
-        .with_extensions(vec!["ts", "tsx", "js", "mjs", "json"])
+        .with_extensions(RESOLVE_EXTENSIONS.to_vec())
         .with_basedir(PathBuf::from(&source_filepath.parent().unwrap()))
+}
+
+/// Resolves `request` to a file, returning any loader query suffix (e.g. `?raw`)
+/// separately.
+fn resolve_module(source_filepath: PathBuf, request: &str) -> (PathBuf, Option<String>) {
+    let (request, query) = split_query(request);
+
+    // Context and glob requests resolve to their directory
+    if query.is_some_and(|query| is_context_query(query) || is_glob_query(query)) {
+        let dir = normalize_path(&source_filepath.parent().unwrap().join(request));
+        return (dir, query.map(String::from));
+    }
+
+    let filepath = create_resolver(&source_filepath)
         .resolve(request)
-        .expect(&format!(
-            "Failed to resolve {} from {:?}",
-            request, &source_filepath
-        ))
+        .unwrap_or_else(|_| panic!("Failed to resolve {} from {:?}", request, &source_filepath));
+
+    let query = query.filter(|query| is_loader_query(query));
+    (filepath, query.map(String::from))
 }
@@ -2,7 +2,7 @@ use std::path::Path;
 
 use swc_common::comments::SingleThreadedComments;
 use swc_common::{sync::Lrc, FileName, SourceMap};
-use swc_ecmascript::ast::Module;
+use swc_ecmascript::ast::{Expr, Module};
 use swc_ecmascript::parser::lexer::Lexer;
 use swc_ecmascript::parser::{PResult, Parser, StringInput, Syntax, TsConfig};
 
@@ -16,12 +16,13 @@ pub fn parse(
         code.into(),
     );
     let comments = SingleThreadedComments::default();
-    let syntax = {
-        let mut tsconfig = TsConfig::default();
-        tsconfig.tsx = true;
-        tsconfig.dynamic_import = true;
-        Syntax::Typescript(tsconfig)
-    };
+    // TypeScript syntax always allows top-level await, which is only valid in
+    // ES modules
+    let syntax = Syntax::Typescript(TsConfig {
+        tsx: true,
+        dynamic_import: true,
+        ..Default::default()
+    });
     let lexer = Lexer::new(
         syntax,
         Default::default(),
@@ -34,3 +35,15 @@ pub fn parse(
         Ok(module) => Ok((module, comments)),
     }
 }
+
+pub fn parse_expr(code: &str, filename: &str, source_map: &Lrc<SourceMap>) -> PResult<Box<Expr>> {
+    let source_file = source_map.new_source_file(FileName::Custom(filename.into()), code.into());
+    let lexer = Lexer::new(
+        Syntax::Es(Default::default()),
+        Default::default(),
+        StringInput::from(&*source_file),
+        None,
+    );
+    let mut parser = Parser::new_from(lexer);
+    parser.parse_expr()
+}
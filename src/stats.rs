@@ -0,0 +1,269 @@
+use crate::utils::to_js_string;
+use crate::Compilation;
+use std::time::Duration;
+
+/// An emitted chunk and the modules bundled into it
+pub struct ChunkStats<'a> {
+    pub filename: String,
+    pub size: usize,
+    pub modules: Vec<&'a String>,
+}
+
+/// Renders a JSON report describing each chunk and each module in it: the
+/// original and output size of a module, which modules import it and how long
+/// it took to build.
+pub fn render_stats_json(c: &Compilation, chunks: &[ChunkStats], build_time: Duration) -> String {
+    let mut module_ids: Vec<&String> = chunks
+        .iter()
+        .flat_map(|chunk| chunk.modules.iter().cloned())
+        .collect();
+    module_ids.sort();
+    module_ids.dedup();
+
+    let chunks_json: Vec<String> = chunks
+        .iter()
+        .map(|chunk| {
+            format!(
+                "    {{\"filename\": {}, \"size\": {}, \"modules\": {}}}",
+                to_js_string(&chunk.filename),
+                chunk.size,
+                to_json_array(chunk.modules.iter().map(|id| id.as_str()))
+            )
+        })
+        .collect();
+
+    let modules_json: Vec<String> = module_ids
+        .iter()
+        .map(|module_id| {
+            let module = c.graph.modules.get(*module_id).expect("Missing module id");
+            let build_time = c
+                .diagnostics
+                .get_module_build_duration(module_id)
+                .unwrap_or_default();
+
+            format!(
+                "    {{\"id\": {}, \"originalSize\": {}, \"outputSize\": {}, \"importers\": {}, \"buildTime\": {}}}",
+                to_js_string(module_id),
+                module.original_size,
+                module.code.len(),
+                to_json_array(c.graph.get_importers(module_id).iter().map(|id| id.as_str())),
+                to_json_millis(build_time)
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"buildTime\": {},\n  \"chunks\": [\n{}\n  ],\n  \"modules\": [\n{}\n  ]\n}}\n",
+        to_json_millis(build_time),
+        chunks_json.join(",\n"),
+        modules_json.join(",\n")
+    )
+}
+
+/// Renders a self-contained HTML page drawing a treemap of module output sizes
+/// for each chunk, from the JSON report.
+pub fn render_stats_html(stats_json: &str) -> String {
+    // Escaped so the report can't close the script tag early
+    let stats_json = stats_json.replace("</", "<\\/");
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset=\"utf-8\">
+    <title>ducttape bundle stats</title>
+    <style>
+      body {{ margin: 0; font: 12px sans-serif; }}
+      h2 {{ margin: 16px 16px 8px; font-size: 14px; }}
+      .treemap {{ position: relative; height: 480px; margin: 0 16px; }}
+      .module {{ position: absolute; box-sizing: border-box; overflow: hidden; padding: 4px; border: 1px solid #fff; background: #8ab4d8; }}
+      .module:hover {{ background: #5b8fbd; }}
+    </style>
+  </head>
+  <body>
+    <script>
+      var stats = {};
+
+      function formatSize(bytes) {{
+        return bytes < 1024 ? bytes + ' B' : (bytes / 1024).toFixed(1) + ' kB';
+      }}
+
+      // Squarified treemap, laying out rows along the shorter side of the remaining area
+      function layout(items, x, y, width, height) {{
+        var total = items.reduce(function (sum, item) {{ return sum + item.size; }}, 0);
+        var rects = [];
+        var scale = total > 0 ? (width * height) / total : 0;
+
+        function worst(row, side) {{
+          var sum = row.reduce(function (s, item) {{ return s + item.size * scale; }}, 0);
+          return row.reduce(function (max, item) {{
+            var area = item.size * scale;
+            return Math.max(max, (side * side * area) / (sum * sum), (sum * sum) / (side * side * area));
+          }}, 0);
+        }}
+
+        var row = [];
+        var remaining = items.slice();
+        while (remaining.length) {{
+          var side = Math.min(width, height);
+          var next = remaining[0];
+          if (row.length === 0 || worst(row.concat(next), side) <= worst(row, side)) {{
+            row.push(remaining.shift());
+            continue;
+          }}
+          var placed = place(row, x, y, width, height);
+          x = placed.x; y = placed.y; width = placed.width; height = placed.height;
+          row = [];
+        }}
+        if (row.length) place(row, x, y, width, height);
+
+        function place(row, x, y, width, height) {{
+          var area = row.reduce(function (s, item) {{ return s + item.size * scale; }}, 0);
+          var horizontal = width >= height;
+          var thickness = horizontal ? area / height : area / width;
+          var offset = 0;
+          row.forEach(function (item) {{
+            var length = (item.size * scale) / thickness;
+            rects.push(horizontal
+              ? {{ item: item, x: x, y: y + offset, width: thickness, height: length }}
+              : {{ item: item, x: x + offset, y: y, width: length, height: thickness }});
+            offset += length;
+          }});
+          return horizontal
+            ? {{ x: x + thickness, y: y, width: width - thickness, height: height }}
+            : {{ x: x, y: y + thickness, width: width, height: height - thickness }};
+        }}
+
+        return rects;
+      }}
+
+      var modulesById = {{}};
+      stats.modules.forEach(function (module) {{ modulesById[module.id] = module; }});
+
+      stats.chunks.forEach(function (chunk) {{
+        var heading = document.createElement('h2');
+        heading.textContent = chunk.filename + ' (' + formatSize(chunk.size) + ')';
+        document.body.appendChild(heading);
+
+        var container = document.createElement('div');
+        container.className = 'treemap';
+        document.body.appendChild(container);
+
+        var items = chunk.modules
+          .map(function (id) {{ return {{ id: id, size: modulesById[id].outputSize }}; }})
+          .filter(function (item) {{ return item.size > 0; }})
+          .sort(function (a, b) {{ return b.size - a.size; }});
+
+        layout(items, 0, 0, container.clientWidth, container.clientHeight).forEach(function (rect) {{
+          var module = modulesById[rect.item.id];
+          var el = document.createElement('div');
+          el.className = 'module';
+          el.style.left = rect.x + 'px';
+          el.style.top = rect.y + 'px';
+          el.style.width = rect.width + 'px';
+          el.style.height = rect.height + 'px';
+          el.textContent = module.id;
+          el.title = module.id + '\\nOutput: ' + formatSize(module.outputSize) + '\\nOriginal: ' + formatSize(module.originalSize) + '\\nImported by: ' + (module.importers.join(', ') || '-');
+          container.appendChild(el);
+        }});
+      }});
+    </script>
+  </body>
+</html>
+",
+        stats_json
+    )
+}
+
+fn to_json_array<'a>(values: impl Iterator<Item = &'a str>) -> String {
+    let values: Vec<String> = values.map(to_js_string).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn to_json_millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::Diagnostics;
+    use crate::js_module::{Dependency, ImportType, JsModule, ModuleExports, ModuleType};
+    use crate::module_graph::ModuleGraph;
+    use crate::Config;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use swc_common::DUMMY_SP;
+
+    fn create_module(id: &str, code: &str, original_size: u64) -> JsModule {
+        JsModule {
+            id: id.to_string(),
+            filepath: PathBuf::from(id),
+            code: code.to_string(),
+            original_size,
+            module_type: ModuleType::ESM,
+            asset: None,
+            exports: ModuleExports::default(),
+            shims: vec![],
+            has_top_level_await: false,
+        }
+    }
+
+    fn create_compilation() -> Compilation {
+        let mut graph = ModuleGraph::new();
+        graph.add_module(create_module("index.js", "require('b.js');", 40));
+        graph.add_module(create_module("b.js", "", 12));
+        graph.add_dependency(
+            "index.js",
+            "b.js",
+            Dependency {
+                request: "./b".into(),
+                import_type: ImportType::Require,
+                span: DUMMY_SP,
+                is_async: false,
+                loc: None,
+            },
+        );
+
+        Compilation {
+            config: Config::default(),
+            diagnostics: Diagnostics::new(),
+            graph,
+            external_requests: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn reports_chunks_and_modules() {
+        let c = create_compilation();
+        let index_id = String::from("index.js");
+        let b_id = String::from("b.js");
+        let chunks = vec![ChunkStats {
+            filename: String::from("main.js"),
+            size: 100,
+            modules: vec![&b_id, &index_id],
+        }];
+
+        let json = render_stats_json(&c, &chunks, Duration::from_micros(1500));
+        assert_eq!(
+            json,
+            "{
+  \"buildTime\": 1.500,
+  \"chunks\": [
+    {\"filename\": \"main.js\", \"size\": 100, \"modules\": [\"b.js\", \"index.js\"]}
+  ],
+  \"modules\": [
+    {\"id\": \"b.js\", \"originalSize\": 12, \"outputSize\": 0, \"importers\": [\"index.js\"], \"buildTime\": 0.000},
+    {\"id\": \"index.js\", \"originalSize\": 40, \"outputSize\": 16, \"importers\": [], \"buildTime\": 0.000}
+  ]
+}
+"
+        );
+    }
+
+    #[test]
+    fn escapes_closing_tags_in_the_html_report() {
+        let html = render_stats_html("{\"id\": \"</script>\"}");
+        assert!(html.contains("var stats = {\"id\": \"<\\/script>\"};"));
+    }
+}
@@ -1,70 +1,702 @@
+use std::collections::HashSet;
+
+use crate::chunks::Chunk;
+use crate::externals::{find_external, get_chunk_externals};
 use crate::js_module::ModuleType;
-use crate::Compilation;
+use crate::utils::{is_identifier_name, relative_path, to_js_string};
+use crate::{Compilation, OutputFormat, Platform};
+
+/// Renders the entry chunk of the main entrypoint in the configured output
+/// format, which loads each of `split_chunks` once one of its entries is
+/// imported
+pub fn render_chunk(chunk: &Chunk, split_chunks: &[Chunk], c: &Compilation) -> String {
+  render(
+    chunk.entries[0],
+    &chunk.modules,
+    &render_chunk_loader(split_chunks),
+    c.config.format,
+    c.config.global_name.as_deref(),
+    c,
+  )
+}
+
+/// Renders the chunk of a worker entrypoint as an ES module when the output
+/// format is, otherwise as a classic script, which every kind of worker can load
+pub fn render_worker_chunk(entry_id: &String, c: &Compilation) -> String {
+  let format = match c.config.format {
+    OutputFormat::Esm => OutputFormat::Esm,
+    _ => OutputFormat::Iife,
+  };
+  let modules_in_chunk = c.graph.get_modules_in_order(entry_id);
+  render(
+    entry_id,
+    &modules_in_chunk,
+    &render_chunk_loader(&[]),
+    format,
+    None,
+    c,
+  )
+}
+
+/// Renders a chunk split from the entry chunk of `entry_id` as an ES module
+/// exporting its modules, which the entry chunk's runtime adds to its own when
+/// it loads the chunk with `import()`. The modules of the shared chunks it
+/// imports are exported along with its own.
+pub fn render_split_chunk(chunk: &Chunk, entry_id: &String, c: &Compilation) -> String {
+  let async_modules = c.graph.get_async_modules(entry_id);
+  let maps = render_module_maps(&chunk.modules, &async_modules, OutputFormat::Esm, c);
+
+  let mut chunk_imports = String::new();
+  for (index, filename) in chunk.imports.iter().enumerate() {
+    chunk_imports.push_str(&format!(
+      "import * as __chunk_{}__ from {};\n",
+      index,
+      to_js_string(&format!("./{}", filename))
+    ));
+  }
+  let merge_imported = |map: &str, name: &str| {
+    if chunk.imports.is_empty() {
+      return map.to_string();
+    }
+    let imported: Vec<String> = (0..chunk.imports.len())
+      .map(|index| format!("__chunk_{}__.{}", index, name))
+      .collect();
+    format!("Object.assign({}, {})", map, imported.join(", "))
+  };
+
+  format!(
+    "{}{}{}{}
+// Only the entry chunk copies its exports, none of which are in this chunk
+function __exportUpdated__(value) {{
+  return value;
+}}
+export var modules = {};
+export var externals = {};
+export var asyncModules = {};
+",
+    maps.external_imports,
+    chunk_imports,
+    RUNTIME_HELPERS,
+    render_process_shim(&chunk.modules, c),
+    merge_imported(&maps.modules, "modules"),
+    merge_imported(&maps.externals, "externals"),
+    merge_imported(&maps.async_modules, "asyncModules")
+  )
+}
 
-pub fn render_chunk(entry_id: &String, c: &Compilation) -> String {
-  let mut modules_in_chunk = c.graph.get_module_deps(&entry_id);
-  modules_in_chunk.insert(entry_id);
+/// Functions the transformed modules call, which are declared in every chunk
+/// with modules
+const RUNTIME_HELPERS: &str = "
+    function __export__(exports, getters) {
+      Object.keys(getters).forEach(function (key) {
+        Object.defineProperty(exports, key, {
+          enumerable: true,
+          get: getters[key]
+        });
+      });
+    }
+    function __exportAll__(exports, target) {
+      Object.keys(target).forEach(function (key) {
+        if (key === 'default') return;
+        // Explicit exports take precedence over star exports
+        if (Object.prototype.hasOwnProperty.call(exports, key)) return;
+        Object.defineProperty(exports, key, {
+          enumerable: true,
+          get: function get() {
+            return target[key];
+          }
+        });
+      });
+    }
+    // Merges the modules matched by each `import.meta.glob` pattern, passed
+    // as pairs of the pattern's directory and its modules
+    function __importGlob__() {
+      var glob = {};
+      for (var i = 0; i < arguments.length; i += 2) {
+        var dir = arguments[i];
+        var modules = arguments[i + 1];
+        Object.keys(modules).forEach(function (key) {
+          glob[dir + key] = modules[key];
+        });
+      }
+      return glob;
+    }
+    function __invalidGlob__(message) {
+      throw new Error(message);
+    }
+    function __dynamicRequire__(request) {
+      throw new Error(\"Cannot find module '\" + request + \"'. Requires only known at runtime aren't bundled\");
+    }";
+
+/// The object literals mapping each module of a chunk to its factory, each
+/// external it requires to a function returning it and each async module to
+/// its imports, with the declarations importing externals natively
+struct ModuleMaps {
+  modules: String,
+  externals: String,
+  async_modules: String,
+  external_imports: String,
+}
 
+fn render_module_maps(
+  modules_in_chunk: &[&String],
+  async_modules: &HashSet<&String>,
+  format: OutputFormat,
+  c: &Compilation,
+) -> ModuleMaps {
   let mut module_map = String::from("{\n");
+  let mut async_modules_map = String::from("{\n");
 
   for module_id in modules_in_chunk {
-    let module = c.graph.modules.get(module_id).expect("Missing module id");
+    let module = c.graph.modules.get(*module_id).expect("Missing module id");
 
-    match module.module_type {
-      ModuleType::CommonJS => {
-        module_map.push_str(&format!("\"{}\": [function(module, require) {{", module.id));
-        module_map.push_str(&module.code);
-        module_map.push_str("},'CJS'],")
-      }
-      ModuleType::ESM => {
-        module_map.push_str(&format!(
-          "\"{}\": [function(exports, require) {{",
-          module.id
-        ));
-        module_map.push_str(&module.code);
-        module_map.push_str("},'ESM'],")
-      }
+    // CommonJS modules receive the same `module` and `exports` as in Node
+    let (exports_params, module_type) = match module.module_type {
+      ModuleType::CommonJS => ("module, exports", "CJS"),
+      ModuleType::ESM => ("exports", "ESM"),
+    };
+    // Node modules are given their source location, relative to the chunk
+    let (node_params, source_path) = match c.config.platform {
+      Platform::Node => (
+        ", __filename, __dirname",
+        format!(
+          ", {}",
+          to_js_string(&relative_path(&c.config.output_dir, &module.filepath))
+        ),
+      ),
+      _ => ("", String::new()),
+    };
+
+    // Modules using top-level await run once their imports are evaluated,
+    // which are awaited in order first
+    if async_modules.contains(module_id) {
+      let imports: Vec<String> = c
+        .graph
+        .get_static_imports(module_id)
+        .map(|id| to_js_string(id))
+        .collect();
+      async_modules_map.push_str(&format!(
+        "{}: [{}],\n",
+        to_js_string(module_id),
+        imports.join(", ")
+      ));
     }
+    let async_keyword = if module.has_top_level_await { "async " } else { "" };
+
+    module_map.push_str(&format!(
+      "\"{}\": [{}function({}, require, __importMeta__{}) {{",
+      module.id, async_keyword, exports_params, node_params
+    ));
+    module_map.push_str(&module.code);
+    module_map.push_str(&format!("}},'{}'{}],", module_type, source_path));
   }
 
   module_map.push_str("\n}");
+  async_modules_map.push('}');
+
+  let mut externals_map = String::from("{\n");
+  let mut external_imports = String::new();
+
+  for (index, request) in get_chunk_externals(c, modules_in_chunk).iter().enumerate() {
+    // Externals without a global, including Node built-ins, are loaded natively
+    let global =
+      find_external(&c.config.externals, request).and_then(|external| external.global.as_ref());
+    let (value, module_type) = match (global, format) {
+      (Some(global), _) => (global.clone(), "CJS"),
+      (None, OutputFormat::Esm) => {
+        external_imports.push_str(&format!(
+          "import * as __external_{}__ from {};\n",
+          index,
+          to_js_string(request)
+        ));
+        (format!("__external_{}__", index), "ESM")
+      }
+      (None, _) => (format!("require({})", to_js_string(request)), "CJS"),
+    };
+
+    externals_map.push_str(&format!(
+      "{}: [function () {{ return {}; }}, '{}'],\n",
+      to_js_string(request),
+      value,
+      module_type
+    ));
+  }
+
+  externals_map.push('}');
 
+  ModuleMaps {
+    modules: module_map,
+    externals: externals_map,
+    async_modules: async_modules_map,
+    external_imports,
+  }
+}
+
+/// A minimal `process` shared by every module of the chunk shimmed for the
+/// browser
+fn render_process_shim(modules_in_chunk: &[&String], c: &Compilation) -> &'static str {
+  let uses_process_shim = modules_in_chunk.iter().any(|module_id| {
+    let module = &c.graph.modules[*module_id];
+    module.shims.iter().any(|shim| shim == "process")
+  });
+  if !uses_process_shim {
+    return "";
+  }
+
+  "var __process__ = {
+      env: {},
+      browser: true,
+      argv: [],
+      version: '',
+      versions: {},
+      platform: 'browser',
+      cwd: function () { return '/'; },
+      nextTick: function (fn) {
+        var args = Array.prototype.slice.call(arguments, 1);
+        Promise.resolve().then(function () { fn.apply(null, args); });
+      }
+    };"
+}
+
+/// Declares `loadChunk`, which loads the split chunk of a module imported with
+/// `import()` and adds its modules to the runtime's. Without split chunks
+/// every module is in the entry chunk already.
+fn render_chunk_loader(split_chunks: &[Chunk]) -> String {
+  if split_chunks.is_empty() {
+    return String::from("var loadChunk = () => Promise.resolve();");
+  }
+
+  let mut chunks_map = String::from("{\n");
+  for chunk in split_chunks {
+    for entry in &chunk.entries {
+      chunks_map.push_str(&format!(
+        "{}: {},\n",
+        to_js_string(entry),
+        to_js_string(&format!("./{}", chunk.filename))
+      ));
+    }
+  }
+  chunks_map.push('}');
+
+  // Relative to the entry chunk, which is the module calling `import()`
   format!(
-    "
-    function __exportAll__(target) {{
-      Object.keys(target).forEach(function (key) {{
-        if (key === 'default') return;
-        if (Object.prototype.hasOwnProperty.call(_exportNames, key)) return;
-        if (key in exports && exports[key] === target[key]) return;
-        Object.defineProperty(exports, key, {{
-          enumerable: true,
-          get: function get() {{
-            return target[key];
-          }}
-        }});
-      }});
+    "var chunks = {};
+      var loadChunk = (moduleName) => !chunks[moduleName] || modules[moduleName]
+        ? Promise.resolve()
+        : import(chunks[moduleName]).then((chunk) => {{
+          Object.assign(modules, chunk.modules);
+          Object.assign(externals, chunk.externals);
+          Object.assign(asyncModules, chunk.asyncModules);
+        }});",
+    chunks_map
+  )
+}
+
+fn render(
+  entry_id: &String,
+  modules_in_chunk: &[&String],
+  chunk_loader: &str,
+  format: OutputFormat,
+  global_name: Option<&str>,
+  c: &Compilation,
+) -> String {
+  let async_modules = c.graph.get_async_modules(entry_id);
+  let maps = render_module_maps(modules_in_chunk, &async_modules, format, c);
+  let process_shim = render_process_shim(modules_in_chunk, c);
+
+  // Module scripts have no `document.currentScript`
+  let base_url = match format {
+    OutputFormat::Esm => "import.meta.url",
+    _ => {
+      "typeof document !== 'undefined' && document.currentScript
+        ? document.currentScript.src
+        : typeof location !== 'undefined'
+          ? location.href
+          : 'file://' + (typeof __filename !== 'undefined' ? __filename : '/')"
+    }
+  };
+
+  let runtime = format!(
+    "{}
+    // Wraps each assignment to an exported binding, so an ES module chunk can
+    // update its own exports, which are copies
+    function __exportUpdated__(value) {{
+      if (onExportUpdated) onExportUpdated();
+      return value;
     }}
     var modules = {};
+    // Outside of ductTape so a native require isn't shadowed by the runtime's
+    var externals = {};
+    var asyncModules = {};
+    {}
     var entry = \"{}\";
     function ductTape({{ modules, entry }}) {{
       var moduleCache = {{}};
-      var interopRequireDefault = (exports, isDefaultImport, isCjs) => isDefaultImport && isCjs ? {{ default: exports }} : exports;
-      var require = (moduleName, isDefaultImport) => {{
-        if (!moduleCache[moduleName]) {{
-          var exports = {{}};
-          modules[moduleName][0](exports, require);
-          moduleCache[moduleName] = modules[moduleName][1] === 'CJS' ? exports.exports : exports;
+      var baseUrl = {};
+      // Relative to the module's source file when it's known, so on Node the
+      // url is the file `__filename` is the path of
+      var createImportMeta = (moduleName) => ({{
+        url: new URL(modules[moduleName][2] || moduleName, baseUrl).href,
+        hot: undefined,
+      }});
+      // Default and namespace imports of CommonJS see `module.exports` as the
+      // default export, unless it was compiled from ESM and flagged `__esModule`
+      var interopRequire = (exports, interop, isCjs) => {{
+        if (!interop || !isCjs || (exports && exports.__esModule)) return exports;
+        var namespace = {{ default: exports }};
+        if (interop === 'namespace' && exports && (typeof exports === 'object' || typeof exports === 'function')) {{
+          Object.keys(exports).forEach(function (key) {{
+            if (key === 'default') return;
+            Object.defineProperty(namespace, key, {{
+              enumerable: true,
+              get: function get() {{
+                return exports[key];
+              }}
+            }});
+          }});
+        }}
+        return namespace;
+      }};
+      var require = (moduleName, interop) => {{
+        if (!modules[moduleName]) {{
+          var external = externals[moduleName];
+          return interopRequire(external[0](), interop, external[1] === 'CJS');
+        }}
+
+        var isCjs = modules[moduleName][1] === 'CJS';
+        var module = moduleCache[moduleName];
+
+        if (!module) {{
+          if (asyncModules[moduleName]) {{
+            throw new Error(\"Module '\" + moduleName + \"' uses top-level await so can't be required synchronously\");
+          }}
+          // Cached before the factory runs so circular requires receive the
+          // partially initialised exports, matching Node
+          module = moduleCache[moduleName] = {{ exports: {{}} }};
+          evaluate(moduleName, module);
+        }}
+
+        return interopRequire(module.exports, interop, isCjs);
+      }};
+      // Runs a module's factory, returning a promise if it uses top-level await
+      var evaluate = (moduleName, module) => {{
+        var filename = modules[moduleName][2] && decodeURIComponent(new URL(modules[moduleName][2], baseUrl).pathname);
+        var args = [require, createImportMeta(moduleName), filename, filename && filename.slice(0, filename.lastIndexOf('/'))];
+        if (modules[moduleName][1] === 'CJS') {{
+          return modules[moduleName][0].apply(module.exports, [module, module.exports].concat(args));
+        }}
+        // Lets CommonJS compiled from ESM, e.g. by Babel, interop with the module
+        Object.defineProperty(module.exports, '__esModule', {{ value: true }});
+        return modules[moduleName][0].apply(null, [module.exports].concat(args));
+      }};
+      // Evaluates an async module after each of its imports, in import order,
+      // so its synchronous requires receive fully evaluated exports. Every
+      // importer waits for the same evaluation, which is cached by module
+      var evaluations = {{}};
+      var requireAsync = (moduleName, importers = []) => {{
+        if (evaluations[moduleName]) {{
+          // A module can't wait for its own importer, so cyclic imports get
+          // the partially initialised exports, as with require
+          return importers.includes(moduleName) ? Promise.resolve() : evaluations[moduleName];
         }}
 
-        return interopRequireDefault(moduleCache[moduleName], isDefaultImport, modules[moduleName][1] === 'CJS');
+        var module = moduleCache[moduleName] = {{ exports: {{}} }};
+        var path = importers.concat(moduleName);
+        return evaluations[moduleName] = asyncModules[moduleName]
+          .reduce((promise, dep) => promise.then(() => asyncModules[dep] ? requireAsync(dep, path) : require(dep)), Promise.resolve())
+          .then(() => evaluate(moduleName, module));
       }};
+      {}
+      // Resolves to a module's exports once its chunk is loaded and it and any
+      // top-level await it uses are evaluated, e.g. for `import()` or when a
+      // lazy glob import is called
+      require.async = (moduleName, interop) => loadChunk(moduleName)
+        .then(() => asyncModules[moduleName] ? requireAsync(moduleName) : undefined)
+        .then(() => require(moduleName, interop));
     
-      // start the program
-      require(entry);
+      // start the program and return the entry's exports, or a promise of
+      // them if the entry uses top-level await
+      return asyncModules[entry] ? requireAsync(entry).then(() => require(entry)) : require(entry);
     }}
-
-    ductTape({{ modules, entry }});
     ",
-    module_map, entry_id
+    RUNTIME_HELPERS,
+    maps.modules,
+    maps.externals,
+    maps.async_modules,
+    process_shim,
+    entry_id,
+    base_url,
+    chunk_loader
+  );
+
+  let is_async_entry = async_modules.contains(entry_id);
+  wrap_chunk(
+    &runtime,
+    &maps.external_imports,
+    entry_id,
+    is_async_entry,
+    format,
+    global_name,
+    c,
   )
 }
+
+/// Wraps the runtime so the entry's exports are exposed in the configured
+/// output format. An async entry exposes a promise of its exports, except as
+/// an ES module which awaits it at the top level.
+fn wrap_chunk(
+  runtime: &str,
+  external_imports: &str,
+  entry_id: &str,
+  is_async_entry: bool,
+  format: OutputFormat,
+  global_name: Option<&str>,
+  c: &Compilation,
+) -> String {
+  let factory = format!(
+    "function (onExportUpdated) {{{}
+    return ductTape({{ modules, entry }});
+  }}",
+    runtime
+  );
+
+  match format {
+    OutputFormat::Iife => match global_name {
+      Some(global_name) => assign_global(None, global_name, &format!("({})()", factory)),
+      None => format!("({})();\n", factory),
+    },
+    OutputFormat::Cjs => format!("module.exports = ({})();\n", factory),
+    OutputFormat::Umd => format!(
+      "(function (root, factory) {{
+  if (typeof define === 'function' && define.amd) {{
+    define([], factory);
+  }} else if (typeof module === 'object' && module.exports) {{
+    module.exports = factory();
+  }} else {{
+    {}
+  }}
+}})(typeof self !== 'undefined' ? self : this, {});\n",
+      assign_global(
+        Some("root"),
+        global_name.expect("UMD output requires a global name"),
+        "factory()"
+      )
+      .trim_end()
+      .replace('\n', "\n    "),
+      factory
+    ),
+    OutputFormat::Esm => {
+      let module = c.graph.modules.get(entry_id).expect("Missing module id");
+      let await_keyword = if is_async_entry { "await " } else { "" };
+
+      match module.module_type {
+        // Export names of CommonJS modules aren't known until they run
+        ModuleType::CommonJS => format!(
+          "{}var __entry__ = {}({})();\nexport default __entry__;\n",
+          external_imports, await_keyword, factory
+        ),
+        // Exports are `let` bindings copied from the entry's exports, and copied
+        // again whenever an exported binding is assigned so they stay live
+        ModuleType::ESM => {
+          let export_names = c.graph.get_export_names(entry_id);
+          let mut locals = vec![];
+          let mut updates = String::new();
+          let mut specifiers = vec![];
+          for (index, name) in export_names.iter().enumerate() {
+            let local = format!("__export_{}__", index);
+            updates.push_str(&format!("  {} = __entry__[{}];\n", local, to_js_string(name)));
+            specifiers.push(format!("{} as {}", local, to_export_name(name)));
+            locals.push(local);
+          }
+
+          if specifiers.is_empty() {
+            return format!(
+              "{}{}({})();\n",
+              external_imports, await_keyword, factory
+            );
+          }
+
+          format!(
+            "{}let {};
+var __entry__;
+function __updateExports__() {{
+  if (!__entry__) return;
+{}}}
+__entry__ = {}({})(__updateExports__);
+__updateExports__();
+export {{ {} }};\n",
+            external_imports,
+            locals.join(", "),
+            updates,
+            await_keyword,
+            factory,
+            specifiers.join(", ")
+          )
+        }
+      }
+    }
+  }
+}
+
+/// Assigns `value` to a global name like `MyLib` or `MyCompany.widgets`,
+/// creating the objects on its path that don't exist yet. Without an object
+/// the first segment is declared as a variable.
+fn assign_global(object: Option<&str>, global_name: &str, value: &str) -> String {
+  let segments: Vec<&str> = global_name.split('.').collect();
+  let (mut target, mut assignments) = match object {
+    Some(object) => (object.to_string(), String::new()),
+    None if segments.len() == 1 => return format!("var {} = {};\n", global_name, value),
+    None => (
+      segments[0].to_string(),
+      format!("var {0} = {0} || {{}};\n", segments[0]),
+    ),
+  };
+
+  let skip = if object.is_some() { 0 } else { 1 };
+  for (index, segment) in segments.iter().enumerate().skip(skip) {
+    target = format!("{}[{}]", target, to_js_string(segment));
+    if index == segments.len() - 1 {
+      assignments.push_str(&format!("{} = {};\n", target, value));
+    } else {
+      assignments.push_str(&format!("{0} = {0} || {{}};\n", target));
+    }
+  }
+  assignments
+}
+
+/// Export names that aren't identifiers, e.g. from `export { x as "a-b" }`,
+/// are written as string literals
+fn to_export_name(name: &str) -> String {
+  if is_identifier_name(name) {
+    name.to_string()
+  } else {
+    to_js_string(name)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::chunks::create_chunk;
+  use crate::diagnostics::Diagnostics;
+  use crate::externals::External;
+  use crate::js_module::{JsModule, ModuleExports};
+  use crate::module_graph::ModuleGraph;
+  use crate::Config;
+  use std::collections::{BTreeSet, HashMap};
+  use std::path::PathBuf;
+
+  fn create_module(id: &str, code: &str) -> JsModule {
+    JsModule {
+      id: id.to_string(),
+      filepath: PathBuf::from(id),
+      code: code.to_string(),
+      original_size: 0,
+      module_type: ModuleType::CommonJS,
+      asset: None,
+      exports: ModuleExports::default(),
+      shims: vec![],
+      has_top_level_await: false,
+    }
+  }
+
+  /// `index.js` and `worker.js` are separate entries and only `index.js`
+  /// requires the external `react`
+  fn create_compilation() -> Compilation {
+    let mut graph = ModuleGraph::new();
+    graph.add_module(create_module("index.js", "module.exports = require('react');"));
+    graph.add_module(create_module("worker.js", ""));
+
+    let mut external_requests = HashMap::new();
+    external_requests.insert(
+      String::from("index.js"),
+      vec![String::from("react")].into_iter().collect::<BTreeSet<_>>(),
+    );
+
+    Compilation {
+      config: Config {
+        externals: vec![External::parse("react=React").unwrap()],
+        ..Config::default()
+      },
+      diagnostics: Diagnostics::new(),
+      graph,
+      external_requests,
+    }
+  }
+
+  #[test]
+  fn reads_global_externals_of_the_chunk() {
+    let c = create_compilation();
+
+    let index_id = String::from("index.js");
+    let chunk = render_chunk(&create_chunk(&c.graph, &index_id, String::new()), &[], &c);
+    assert!(chunk.contains("\"react\": [function () { return React; }, 'CJS'],"));
+
+    let worker_id = String::from("worker.js");
+    let chunk = render_chunk(&create_chunk(&c.graph, &worker_id, String::new()), &[], &c);
+    assert!(!chunk.contains("React"));
+  }
+
+  #[test]
+  fn loads_split_chunks_with_native_import() {
+    let c = create_compilation();
+    let index_id = String::from("index.js");
+    let worker_id = String::from("worker.js");
+    let split_chunk = Chunk {
+      filename: String::from("page.1234.js"),
+      modules: vec![&worker_id],
+      entries: vec![&worker_id],
+      imports: vec![String::from("shared.5678.js")],
+    };
+
+    let chunk = render_chunk(
+      &create_chunk(&c.graph, &index_id, String::new()),
+      &[split_chunk],
+      &c,
+    );
+    assert!(chunk.contains("\"worker.js\": \"./page.1234.js\","));
+    assert!(chunk.contains(": import(chunks[moduleName]).then((chunk) => {"));
+
+    // Without split chunks every module is already in the chunk
+    let chunk = render_chunk(&create_chunk(&c.graph, &index_id, String::new()), &[], &c);
+    assert!(chunk.contains("var loadChunk = () => Promise.resolve();"));
+  }
+
+  #[test]
+  fn exports_the_modules_of_split_chunks_with_those_they_import() {
+    let c = create_compilation();
+    let index_id = String::from("index.js");
+    let worker_id = String::from("worker.js");
+    let split_chunk = Chunk {
+      filename: String::from("page.1234.js"),
+      modules: vec![&worker_id],
+      entries: vec![&worker_id],
+      imports: vec![String::from("shared.5678.js")],
+    };
+
+    let chunk = render_split_chunk(&split_chunk, &index_id, &c);
+    assert!(chunk.starts_with("import * as __chunk_0__ from \"./shared.5678.js\";\n"));
+    assert!(chunk.contains("export var modules = Object.assign({\n\"worker.js\": [function(module, exports"));
+    assert!(chunk.contains("}, __chunk_0__.modules);\n"));
+    assert!(chunk.contains("export var asyncModules = Object.assign({\n}, __chunk_0__.asyncModules);\n"));
+  }
+
+  #[test]
+  fn assigns_dotted_global_names() {
+    assert_eq!(assign_global(None, "MyLib", "value"), "var MyLib = value;\n");
+    assert_eq!(
+      assign_global(None, "MyCompany.widgets", "value"),
+      "var MyCompany = MyCompany || {};\nMyCompany[\"widgets\"] = value;\n"
+    );
+    assert_eq!(
+      assign_global(Some("root"), "MyCompany.widgets", "value"),
+      "root[\"MyCompany\"] = root[\"MyCompany\"] || {};\nroot[\"MyCompany\"][\"widgets\"] = value;\n"
+    );
+  }
+
+  #[test]
+  fn quotes_export_names_that_are_not_identifiers() {
+    assert_eq!(to_export_name("default"), "default");
+    assert_eq!(to_export_name("a-b"), "\"a-b\"");
+  }
+}
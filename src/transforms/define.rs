@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use ast::*;
+use swc_common::sync::Lrc;
+use swc_common::SourceMap;
+use swc_ecmascript::ast;
+use swc_ecmascript::utils::DropSpan;
+use swc_ecmascript::visit::{Fold, FoldWith, VisitMutWith};
+
+use crate::parser::parse_expr;
+
+pub type Defines = HashMap<String, Expr>;
+
+/// Parses each define value as a JS expression, e.g. `"production"` or `false`.
+pub fn parse_defines(define: &HashMap<String, String>) -> Result<Defines, String> {
+    let source_map = Lrc::new(SourceMap::default());
+    let mut defines = HashMap::new();
+
+    for (key, value) in define {
+        let mut expr = parse_expr(value, key, &source_map)
+            .map_err(|_| format!("Invalid define value for {}: {}", key, value))?;
+        expr.visit_mut_with(&mut DropSpan {
+            preserve_ctxt: false,
+        });
+        defines.insert(key.clone(), *expr);
+    }
+
+    Ok(defines)
+}
+
+/// Replaces identifiers and member expressions such as `process.env.NODE_ENV`
+/// with their configured values. Bindings that shadow a define are not tracked.
+pub fn define(defines: &Defines) -> impl Fold + '_ {
+    DefineReplacer { defines }
+}
+
+struct DefineReplacer<'a> {
+    defines: &'a Defines,
+}
+
+impl<'a> Fold for DefineReplacer<'a> {
+    fn fold_expr(&mut self, node: Expr) -> Expr {
+        if self.defines.is_empty() {
+            return node;
+        }
+
+        if let Some(path) = expr_path(&node) {
+            if let Some(value) = self.defines.get(&path) {
+                return value.clone();
+            }
+        }
+
+        match node {
+            // Non-computed props are names rather than references
+            Expr::Member(member) if !member.computed => Expr::Member(MemberExpr {
+                obj: member.obj.fold_with(self),
+                ..member
+            }),
+            _ => node.fold_children_with(self),
+        }
+    }
+
+    // `{ __DEV__ }` is shorthand for `{ __DEV__: __DEV__ }`
+    fn fold_prop(&mut self, node: Prop) -> Prop {
+        match node {
+            Prop::Shorthand(ident) => match self.defines.get(&*ident.sym) {
+                Some(value) => Prop::KeyValue(KeyValueProp {
+                    key: PropName::Ident(ident),
+                    value: Box::new(value.clone()),
+                }),
+                None => Prop::Shorthand(ident),
+            },
+            _ => node.fold_children_with(self),
+        }
+    }
+
+    fn fold_assign_expr(&mut self, node: AssignExpr) -> AssignExpr {
+        // Leave a define that's assigned to alone so `process.env.X = y` stays
+        // valid, while still replacing defines within the target, e.g. in a
+        // computed prop
+        let left = match node.left {
+            PatOrExpr::Expr(expr) if self.is_defined(&expr) => PatOrExpr::Expr(expr),
+            PatOrExpr::Pat(pat) if matches!(&*pat, Pat::Expr(expr) if self.is_defined(expr)) => {
+                PatOrExpr::Pat(pat)
+            }
+            left => left.fold_with(self),
+        };
+
+        AssignExpr {
+            left,
+            right: node.right.fold_with(self),
+            ..node
+        }
+    }
+}
+
+impl<'a> DefineReplacer<'a> {
+    fn is_defined(&self, expr: &Expr) -> bool {
+        expr_path(expr).is_some_and(|path| self.defines.contains_key(&path))
+    }
+}
+
+/// Returns the dotted path of an identifier or static member expression.
+fn expr_path(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Ident(ident) => Some(ident.sym.to_string()),
+        Expr::MetaProp(meta) => Some(format!("{}.{}", meta.meta.sym, meta.prop.sym)),
+        Expr::Member(member) => {
+            let obj = match &member.obj {
+                ExprOrSuper::Expr(obj) => expr_path(obj)?,
+                ExprOrSuper::Super(_) => return None,
+            };
+            let prop = match (&*member.prop, member.computed) {
+                (Expr::Ident(ident), false) => &ident.sym,
+                (Expr::Lit(Lit::Str(str)), true) => &str.value,
+                _ => return None,
+            };
+            Some(format!("{}.{}", obj, prop))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_loader::emit;
+    use crate::parser::parse;
+
+    fn transform(code: &str, define: &[(&str, &str)]) -> String {
+        let define: HashMap<String, String> = define
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let defines = parse_defines(&define).unwrap();
+        let source_map = Lrc::new(SourceMap::default());
+        let (module, comments) = parse(code, "test.js", &source_map).unwrap();
+        let module = module.fold_with(&mut super::define(&defines));
+        String::from_utf8(emit(&module, source_map, comments).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn parses_values_as_expressions() {
+        let mut define = HashMap::new();
+        define.insert(String::from("__DEV__"), String::from("false"));
+        define.insert(String::from("VERSION"), String::from("'1.0.0'"));
+        let defines = parse_defines(&define).unwrap();
+
+        assert!(matches!(
+            defines["__DEV__"],
+            Expr::Lit(Lit::Bool(Bool { value: false, .. }))
+        ));
+        assert!(matches!(&defines["VERSION"], Expr::Lit(Lit::Str(str)) if &*str.value == "1.0.0"));
+    }
+
+    #[test]
+    fn rejects_values_that_are_not_expressions() {
+        let mut define = HashMap::new();
+        define.insert(String::from("__DEV__"), String::from("if ("));
+
+        assert_eq!(
+            parse_defines(&define).err(),
+            Some(String::from("Invalid define value for __DEV__: if ("))
+        );
+    }
+
+    #[test]
+    fn replaces_identifiers_and_member_paths() {
+        let code = transform(
+            "console.log(__DEV__, process.env.NODE_ENV, process.env['NODE_ENV'], config.__DEV__);",
+            &[
+                ("__DEV__", "false"),
+                ("process.env.NODE_ENV", "'production'"),
+            ],
+        );
+        assert_eq!(
+            code,
+            "console.log(false, \"production\", \"production\", config.__DEV__);\n"
+        );
+    }
+
+    #[test]
+    fn expands_shorthand_props() {
+        let code = transform("const flags = { __DEV__, other };", &[("__DEV__", "false")]);
+        assert!(code.contains("__DEV__: false,"));
+        assert!(code.contains("other\n"));
+    }
+
+    #[test]
+    fn leaves_only_defines_that_are_assigned_to() {
+        let code = transform(
+            "process.env.NODE_ENV = 'test';\n\
+             flags[__DEV__] = process.env.NODE_ENV;",
+            &[
+                ("__DEV__", "false"),
+                ("process.env.NODE_ENV", "'production'"),
+            ],
+        );
+        assert!(code.contains("process.env.NODE_ENV = 'test';"));
+        assert!(code.contains("flags[false] = \"production\";"));
+    }
+
+    #[test]
+    fn replaces_bindings_shadowing_a_define() {
+        // Scopes aren't tracked, so a parameter with the name of a define is
+        // replaced too
+        let code = transform(
+            "function log(__DEV__) { return __DEV__; }",
+            &[("__DEV__", "false")],
+        );
+        assert!(code.contains("return false;"));
+    }
+}
@@ -1 +1,4 @@
+pub mod define;
+pub mod node_shims;
 pub mod runtime_imports;
+pub mod top_level_await;
@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+
+use ast::*;
+use swc_atoms::JsWord;
+use swc_common::DUMMY_SP;
+use swc_ecmascript::ast;
+use swc_ecmascript::utils::find_ids;
+use swc_ecmascript::visit::{Node, Visit, VisitWith};
+
+use crate::transforms::runtime_imports::{create_ident_expr, create_require_call, create_var_decl};
+
+/// Declares the Node globals in `shim_globals` that a module references
+/// without declaring them at the top level, for modules running in the browser.
+/// Globals are skipped when `can_shim` returns false, e.g. as their polyfill
+/// isn't installed. Returns the names of the globals that were shimmed.
+pub fn node_shims(
+    module: Module,
+    shim_globals: &[String],
+    can_shim: impl Fn(&str) -> bool,
+) -> (Module, Vec<String>) {
+    let mut collector = GlobalsCollector {
+        referenced: HashSet::new(),
+    };
+    module.visit_with(&Invalid { span: DUMMY_SP } as _, &mut collector);
+    let declared = get_top_level_decls(&module);
+
+    let shims: Vec<String> = shim_globals
+        .iter()
+        .filter(|name| {
+            let name: JsWord = name.as_str().into();
+            collector.referenced.contains(&name) && !declared.contains(&name) && can_shim(&name)
+        })
+        .cloned()
+        .collect();
+
+    let mut body: Vec<ModuleItem> = shims.iter().filter_map(|name| create_shim(name)).collect();
+
+    if body.is_empty() {
+        return (module, shims);
+    }
+
+    body.extend(module.body);
+    (Module { body, ..module }, shims)
+}
+
+fn create_shim(name: &str) -> Option<ModuleItem> {
+    let init = match name {
+        "global" => create_ident_expr(&"globalThis".into()),
+        // Defined once by the runtime so every module shares the same object
+        "process" => create_ident_expr(&"__process__".into()),
+        "Buffer" => Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::Call(create_require_call(&"buffer".into())))),
+            prop: Box::new(create_ident_expr(&"Buffer".into())),
+            computed: false,
+        }),
+        _ => return None,
+    };
+
+    Some(create_var_decl(&name.into(), Box::new(init)))
+}
+
+fn get_top_level_decls(module: &Module) -> HashSet<JsWord> {
+    let mut declared = HashSet::new();
+
+    for module_item in &module.body {
+        let decl = match module_item {
+            ModuleItem::Stmt(Stmt::Decl(decl)) => decl,
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => &export.decl,
+            ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+                for specifier in &import.specifiers {
+                    let local = match specifier {
+                        ImportSpecifier::Named(named) => &named.local,
+                        ImportSpecifier::Default(default) => &default.local,
+                        ImportSpecifier::Namespace(namespace) => &namespace.local,
+                    };
+                    declared.insert(local.sym.clone());
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        match decl {
+            Decl::Fn(fn_decl) => {
+                declared.insert(fn_decl.ident.sym.clone());
+            }
+            Decl::Class(class_decl) => {
+                declared.insert(class_decl.ident.sym.clone());
+            }
+            Decl::Var(var) => {
+                let ids: Vec<Ident> = find_ids(&var.decls);
+                declared.extend(ids.into_iter().map(|ident| ident.sym));
+            }
+            _ => {}
+        }
+    }
+
+    declared
+}
+
+/// Collects every identifier that is referenced. Nested scopes aren't tracked
+/// as a shim declared at the top level is still shadowed by a local binding.
+struct GlobalsCollector {
+    referenced: HashSet<JsWord>,
+}
+
+impl Visit for GlobalsCollector {
+    fn visit_expr(&mut self, node: &Expr, _parent: &dyn Node) {
+        if let Expr::Ident(ident) = node {
+            self.referenced.insert(ident.sym.clone());
+        }
+        node.visit_children_with(self);
+    }
+
+    // `typeof X` is how code checks for a global, so doesn't need it shimmed
+    fn visit_unary_expr(&mut self, node: &UnaryExpr, _parent: &dyn Node) {
+        if node.op == UnaryOp::TypeOf && matches!(*node.arg, Expr::Ident(_)) {
+            return;
+        }
+        node.visit_children_with(self);
+    }
+
+    fn visit_member_expr(&mut self, node: &MemberExpr, _parent: &dyn Node) {
+        node.obj.visit_with(node as _, self);
+        // Non-computed props are names rather than references
+        if node.computed {
+            node.prop.visit_with(node as _, self);
+        }
+    }
+
+    fn visit_prop(&mut self, node: &Prop, _parent: &dyn Node) {
+        if let Prop::Shorthand(ident) = node {
+            self.referenced.insert(ident.sym.clone());
+        }
+        node.visit_children_with(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_loader::emit;
+    use crate::parser::parse;
+    use swc_common::sync::Lrc;
+    use swc_common::SourceMap;
+
+    fn transform(code: &str, can_shim: impl Fn(&str) -> bool) -> (String, Vec<String>) {
+        let source_map = Lrc::new(SourceMap::default());
+        let (module, comments) = parse(code, "test.js", &source_map).unwrap();
+        let globals = ["global", "process", "Buffer"].map(String::from);
+        let (module, shims) = node_shims(module, &globals, can_shim);
+        let code = String::from_utf8(emit(&module, source_map, comments).unwrap()).unwrap();
+        (code, shims)
+    }
+
+    #[test]
+    fn declares_referenced_globals() {
+        let (code, shims) = transform("process.env.DEBUG && Buffer.from(x);", |_| true);
+        assert_eq!(shims, ["process", "Buffer"]);
+        assert!(code
+            .starts_with("var process = __process__;\nvar Buffer = require(\"buffer\").Buffer;\n"));
+    }
+
+    #[test]
+    fn skips_declared_and_typeof_checked_globals() {
+        let (code, shims) = transform(
+            "import process from 'process';\nif (typeof global !== 'undefined') {}\nx.Buffer;",
+            |_| true,
+        );
+        assert!(shims.is_empty());
+        assert!(!code.contains("var "));
+    }
+
+    #[test]
+    fn skips_globals_that_cant_be_shimmed() {
+        let (_, shims) = transform("global.x = Buffer.from(process.argv);", |name| {
+            name != "Buffer"
+        });
+        assert_eq!(shims, ["global", "process"]);
+    }
+}
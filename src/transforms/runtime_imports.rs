@@ -1,18 +1,44 @@
 use crate::utils::strip_invalid_chars;
 use ast::*;
+use std::collections::{HashMap, HashSet};
 use swc_atoms::JsWord;
-use swc_common::DUMMY_SP;
+use swc_common::{Span, Spanned, DUMMY_SP};
 use swc_ecmascript::ast;
-use swc_ecmascript::visit::{Fold, FoldWith};
+use swc_ecmascript::utils::ident::IdentLike;
+use swc_ecmascript::utils::{find_ids, Id};
+use swc_ecmascript::visit::{Fold, FoldWith, VisitMut, VisitMutWith};
 
 use crate::js_module::Dependency;
-use crate::js_module::{ImportType, ModuleType, NamedImport};
+use crate::js_module::{ImportType, ModuleExports, ModuleType, NamedImport};
 
-pub fn runtime_imports(module: ast::Module) -> (Module, Vec<Dependency>, ModuleType) {
+/// The span of an `import.meta.glob` call that can't be expanded, with the reason
+pub type InvalidGlobCall = (Span, String);
+
+/// Replaces imports and requires with runtime requires, returning the module's
+/// dependencies, type and exports along with the span of each require that
+/// can't be bundled and of each `import.meta.glob` call that can't be expanded,
+/// with the reason. Workers are started as module workers if `module_workers`
+/// is set, as their chunks are ES modules.
+pub fn runtime_imports(
+    module: ast::Module,
+    module_workers: bool,
+) -> (
+    Module,
+    Vec<Dependency>,
+    ModuleType,
+    ModuleExports,
+    Vec<Span>,
+    Vec<InvalidGlobCall>,
+) {
     let mut import_mapper = RuntimeImportMapper {
         dependencies: vec![],
+        import_bindings: HashMap::new(),
+        dynamic_requires: vec![],
+        invalid_globs: vec![],
         // Default to CJS until import/export is detected
         module_type: ModuleType::CommonJS,
+        exports: ModuleExports::default(),
+        module_workers,
     };
 
     let transformed_module = module.fold_with(&mut import_mapper);
@@ -21,217 +47,227 @@ pub fn runtime_imports(module: ast::Module) -> (Module, Vec<Dependency>, ModuleT
         transformed_module,
         import_mapper.dependencies,
         import_mapper.module_type,
+        import_mapper.exports,
+        import_mapper.dynamic_requires,
+        import_mapper.invalid_globs,
     )
 }
 
 struct RuntimeImportMapper {
     dependencies: Vec<Dependency>,
+    /// Local name of each named or default import, mapped to the variable
+    /// holding its module's exports and the name it's imported as
+    import_bindings: HashMap<Id, (JsWord, JsWord)>,
+    dynamic_requires: Vec<Span>,
+    invalid_globs: Vec<InvalidGlobCall>,
     module_type: ModuleType,
+    exports: ModuleExports,
+    module_workers: bool,
 }
 
 impl Fold for RuntimeImportMapper {
     fn fold_module(&mut self, node: Module) -> Module {
-        let mut node = node.fold_children_with(self);
+        let node = node.fold_children_with(self);
+        let mut body = Vec::with_capacity(node.body.len());
+        let mut export_getters: Vec<(JsWord, Expr)> = vec![];
 
-        // Remove all import statements
-        node.body.retain(|module_item| match module_item {
-            ModuleItem::ModuleDecl(decl) => match decl {
-                ModuleDecl::Import(_) => false,
-                _ => true,
-            },
-            _ => true,
-        });
+        for module_item in node.body {
+            let decl = match module_item {
+                ModuleItem::ModuleDecl(decl) => decl,
+                ModuleItem::Stmt(_) => {
+                    body.push(module_item);
+                    continue;
+                }
+            };
+
+            // Detecting a ModuleDecl means the current file is ESM
+            self.module_type = ModuleType::ESM;
 
-        for i in 0..node.body.len() {
-            let mut exports = Vec::new();
-
-            match &node.body[i] {
-                ModuleItem::ModuleDecl(decl) => {
-                    // Detecting a ModuleDecl means the current file is ESM
-                    self.module_type = ModuleType::ESM;
-                    match decl {
-                        ModuleDecl::ExportNamed(export) => {
-                            for specifier in &export.specifiers {
-                                match specifier {
-                                    ExportSpecifier::Namespace(namespace) => {
-                                        if let Some(src) = &export.src {
-                                            self.dependencies.push(Dependency {
-                                                request: src.value.clone(),
-                                                import_type: ImportType::Namespace(
-                                                    namespace.name.sym.clone(),
-                                                ),
-                                            })
-                                        } else {
-                                            panic!("Invalid syntax: Namespace export must have src")
-                                        }
-
-                                        exports.push(create_runtime_export(
-                                            &namespace.name.sym,
-                                            &Box::new(Expr::Ident(namespace.name.clone())),
-                                        ));
-                                    }
-                                    ExportSpecifier::Default(default_export) => {
-                                        if let Some(src) = &export.src {
-                                            self.dependencies.push(Dependency {
-                                                request: src.value.clone(),
-                                                import_type: ImportType::Default(
-                                                    default_export.exported.sym.clone(),
-                                                ),
-                                            })
-                                        }
-
-                                        exports.push(create_runtime_export(
-                                            &default_export.exported.sym,
-                                            &Box::new(Expr::Ident(default_export.exported.clone())),
-                                        ));
-                                    }
-                                    ExportSpecifier::Named(named_export) => {
-                                        if let Some(src) = &export.src {
-                                            self.dependencies.push(Dependency {
-                                                request: src.value.clone(),
-                                                // TODO: Group all named imports/exports from the same module into the same Dependency
-                                                import_type: ImportType::Named(vec![NamedImport {
-                                                    local: named_export.orig.sym.clone(),
-                                                    import_name: named_export.orig.sym.clone(),
-                                                }]),
-                                            })
-                                        }
-
-                                        let export_ident;
-
-                                        if let Some(exported) = &named_export.exported {
-                                            export_ident = exported;
-                                        } else {
-                                            export_ident = &named_export.orig;
-                                        }
-
-                                        exports.push(create_runtime_export(
-                                            &export_ident.sym,
-                                            &Box::new(Expr::Ident(named_export.orig.clone())),
-                                        ));
-                                    }
-                                }
+            match decl {
+                // Imports are replaced by the runtime requires inserted below
+                ModuleDecl::Import(_) => {}
+                ModuleDecl::ExportNamed(export) => {
+                    let namespace_local = export
+                        .src
+                        .as_ref()
+                        .map(|src| self.add_reexport_namespace(src));
+
+                    for specifier in &export.specifiers {
+                        let (exported, orig): (JsWord, Option<Ident>) = match specifier {
+                            ExportSpecifier::Namespace(namespace) => {
+                                (namespace.name.sym.clone(), None)
                             }
-                        }
-                        ModuleDecl::ExportDecl(named_export) => match &named_export.decl {
-                            Decl::Var(var_decl) => {
-                                exports.push(create_runtime_export(
-                                    match &var_decl.decls[0].name {
-                                        Pat::Ident(ident) => &ident.id.sym,
-                                        _ => panic!("Not implemented"),
-                                    },
-                                    var_decl.decls[0]
-                                        .init
-                                        .as_ref()
-                                        .expect("export const with no initialiser"),
-                                ));
+                            ExportSpecifier::Default(default_export) => (
+                                default_export.exported.sym.clone(),
+                                Some(Ident {
+                                    span: DUMMY_SP,
+                                    optional: false,
+                                    sym: "default".into(),
+                                }),
+                            ),
+                            ExportSpecifier::Named(named_export) => (
+                                named_export
+                                    .exported
+                                    .as_ref()
+                                    .unwrap_or(&named_export.orig)
+                                    .sym
+                                    .clone(),
+                                Some(named_export.orig.clone()),
+                            ),
+                        };
+
+                        // Re-exports read through the source module's exports to stay live
+                        let value = match (&namespace_local, orig) {
+                            (Some(namespace_local), Some(orig)) => {
+                                create_member_expr(namespace_local, &orig.sym)
                             }
-                            Decl::Fn(fn_decl) => {
-                                exports.push(create_runtime_export(
-                                    &fn_decl.ident.sym,
-                                    &Box::new(Expr::Fn(FnExpr {
-                                        ident: Some(fn_decl.ident.clone()),
-                                        function: fn_decl.function.clone(),
-                                    })),
-                                ));
+                            (Some(namespace_local), None) => create_ident_expr(namespace_local),
+                            // Keeps the binding's context so an exported import is rewritten
+                            (None, Some(orig)) => Expr::Ident(orig),
+                            (None, None) => {
+                                panic!("Invalid syntax: Namespace export must have src")
                             }
-                            _ => {}
-                        },
-                        ModuleDecl::ExportDefaultExpr(default_export) => {
-                            let export_name: JsWord = "default".into();
-                            exports.push(create_runtime_export(&export_name, &default_export.expr));
-                        }
-                        ModuleDecl::ExportAll(export_all) => {
-                            let export_local =
-                                format!("namespace_{}", strip_invalid_chars(&export_all.src.value));
+                        };
 
-                            self.dependencies.push(Dependency {
-                                request: export_all.src.value.clone(),
-                                import_type: ImportType::Namespace(export_local.clone().into()),
-                            });
+                        export_getters.push((exported, value));
+                    }
+                }
+                ModuleDecl::ExportDecl(export) => {
+                    for ident in get_exported_decl_idents(&export.decl) {
+                        export_getters.push((ident.sym.clone(), Expr::Ident(ident)));
+                    }
 
-                            exports.push(Box::new(Expr::Call(CallExpr {
-                                span: DUMMY_SP,
-                                type_args: None,
-                                callee: ExprOrSuper::Expr(Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    optional: false,
-                                    sym: "__exportAll__".into(),
-                                }))),
-                                args: vec![ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        optional: false,
-                                        sym: export_local.into(),
-                                    })),
-                                }],
-                            })));
+                    body.push(ModuleItem::Stmt(Stmt::Decl(export.decl)));
+                }
+                ModuleDecl::ExportDefaultDecl(default_export) => {
+                    // Anonymous declarations are named so they are still hoisted
+                    let default_ident = |ident: Option<Ident>| {
+                        ident.unwrap_or_else(|| Ident {
+                            span: DUMMY_SP,
+                            optional: false,
+                            sym: "__default__".into(),
+                        })
+                    };
+
+                    let decl = match default_export.decl {
+                        DefaultDecl::Fn(fn_expr) => Decl::Fn(FnDecl {
+                            ident: default_ident(fn_expr.ident),
+                            declare: false,
+                            function: fn_expr.function,
+                        }),
+                        DefaultDecl::Class(class_expr) => Decl::Class(ClassDecl {
+                            ident: default_ident(class_expr.ident),
+                            declare: false,
+                            class: class_expr.class,
+                        }),
+                        DefaultDecl::TsInterfaceDecl(interface) => Decl::TsInterface(interface),
+                    };
+
+                    match &decl {
+                        Decl::Fn(FnDecl { ident, .. }) | Decl::Class(ClassDecl { ident, .. }) => {
+                            export_getters.push(("default".into(), Expr::Ident(ident.clone())));
                         }
                         _ => {}
                     }
+
+                    body.push(ModuleItem::Stmt(Stmt::Decl(decl)));
                 }
-                _ => {}
-            };
+                ModuleDecl::ExportDefaultExpr(default_export) => {
+                    let default_local: JsWord = "__default__".into();
 
-            if exports.len() > 0 {
-                node.body[i] = ModuleItem::Stmt(Stmt::Expr(ExprStmt {
-                    span: DUMMY_SP,
-                    expr: Box::new(Expr::Seq(SeqExpr {
+                    body.push(create_var_decl(&default_local, default_export.expr));
+                    export_getters.push(("default".into(), create_ident_expr(&default_local)));
+                }
+                ModuleDecl::ExportAll(export_all) => {
+                    let namespace_local = self.add_reexport_namespace(&export_all.src);
+                    self.exports
+                        .star_requests
+                        .push(export_all.src.value.clone());
+
+                    body.push(ModuleItem::Stmt(Stmt::Expr(ExprStmt {
                         span: DUMMY_SP,
-                        exprs: exports,
-                    })),
-                }))
+                        expr: Box::new(create_runtime_call(
+                            "__exportAll__",
+                            vec![
+                                create_ident_expr(&"exports".into()),
+                                create_ident_expr(&namespace_local),
+                            ],
+                        )),
+                    })));
+                }
+                decl => body.push(ModuleItem::ModuleDecl(decl)),
             }
         }
 
-        let mut runtime_imports: Vec<ModuleItem> = self
-            .dependencies
+        self.exports.names = export_getters
             .iter()
-            .filter(|import| match import.import_type {
-                ImportType::Require => false,
-                _ => true,
+            .map(|(name, _)| name.clone())
+            .collect();
+        let exported_bindings: HashSet<Id> = export_getters
+            .iter()
+            .filter_map(|(_, value)| match value {
+                Expr::Ident(ident) => Some(ident.to_id()),
+                _ => None,
             })
-            .map(|import| self.create_runtime_require(import))
             .collect();
 
+        let mut module_body = Vec::with_capacity(body.len() + self.dependencies.len() + 1);
+
+        // Export getters are defined before anything else runs so that importers,
+        // including circular ones, always read the current value of a binding
+        if !export_getters.is_empty() {
+            module_body.push(create_export_getters(export_getters));
+        }
+
         // Insert runtime imports at start of file
-        runtime_imports.append(&mut node.body);
-        node.body = runtime_imports;
+        module_body.extend(
+            self.dependencies
+                .iter()
+                .enumerate()
+                .filter(|(_, import)| !matches!(import.import_type, ImportType::Require))
+                .map(|(index, import)| self.create_runtime_require(index, import)),
+        );
+        module_body.append(&mut body);
 
-        node
+        if !self.import_bindings.is_empty() {
+            module_body.visit_mut_with(&mut ImportBindingRewriter {
+                bindings: &self.import_bindings,
+            });
+        }
+        if !exported_bindings.is_empty() {
+            module_body.visit_mut_with(&mut ExportUpdateNotifier {
+                bindings: &exported_bindings,
+            });
+        }
+
+        Module {
+            body: module_body,
+            ..node
+        }
     }
 
     fn fold_import_decl(&mut self, node: ImportDecl) -> ImportDecl {
-        if node.specifiers.len() == 0 {
+        if node.specifiers.is_empty() {
             // No specifiers means a side effect import
             self.dependencies.push(Dependency {
                 request: node.src.value.clone(),
                 import_type: ImportType::SideEffect,
+                span: node.span,
+                is_async: false,
+                loc: None,
             });
 
             return node;
         }
 
         let mut namespace = None;
-        let mut named: Vec<NamedImport> = vec![];
+        let mut named: Vec<&ImportNamedSpecifier> = vec![];
         let mut default = None;
 
         for specifier in &node.specifiers {
             match specifier {
-                ImportSpecifier::Default(default_import) => {
-                    default = Some(default_import.local.sym.clone());
-                }
-                ImportSpecifier::Named(named_import) => {
-                    let imported = match &named_import.imported {
-                        Some(i) => i.sym.clone(),
-                        None => named_import.local.sym.clone(),
-                    };
-                    named.push(NamedImport {
-                        local: named_import.local.sym.clone(),
-                        import_name: imported,
-                    })
-                }
+                ImportSpecifier::Default(default_import) => default = Some(&default_import.local),
+                ImportSpecifier::Named(named_import) => named.push(named_import),
                 ImportSpecifier::Namespace(ns_import) => {
                     namespace = Some(ns_import.local.sym.clone())
                 }
@@ -242,130 +278,329 @@ impl Fold for RuntimeImportMapper {
             self.dependencies.push(Dependency {
                 request: node.src.value.clone(),
                 import_type: ImportType::Namespace(namespace_local),
+                span: node.span,
+                is_async: false,
+                loc: None,
             });
         }
 
         if let Some(default_local) = default {
+            let namespace_local = import_local(self.dependencies.len());
+            self.import_bindings
+                .insert(default_local.to_id(), (namespace_local, "default".into()));
             self.dependencies.push(Dependency {
                 request: node.src.value.clone(),
-                import_type: ImportType::Default(default_local),
+                import_type: ImportType::Default(default_local.sym.clone()),
+                span: node.span,
+                is_async: false,
+                loc: None,
             });
         }
 
-        if named.len() > 0 {
+        if !named.is_empty() {
+            let namespace_local = import_local(self.dependencies.len());
+            let named = named
+                .into_iter()
+                .map(|named_import| {
+                    let import_name = named_import
+                        .imported
+                        .as_ref()
+                        .unwrap_or(&named_import.local)
+                        .sym
+                        .clone();
+                    self.import_bindings.insert(
+                        named_import.local.to_id(),
+                        (namespace_local.clone(), import_name.clone()),
+                    );
+                    NamedImport {
+                        local: named_import.local.sym.clone(),
+                        import_name,
+                    }
+                })
+                .collect();
             self.dependencies.push(Dependency {
                 request: node.src.value.clone(),
                 import_type: ImportType::Named(named),
+                span: node.span,
+                is_async: false,
+                loc: None,
             });
         }
 
         node
     }
 
-    // CommonJS Support
-    fn fold_call_expr(&mut self, node: CallExpr) -> CallExpr {
-        let require_ident: JsWord = "require".into();
-
-        let is_require_call = match node.callee.clone() {
-            ExprOrSuper::Expr(callee_expr) => match &*callee_expr {
-                Expr::Ident(ident) => {
-                    if ident.sym == require_ident {
-                        true
-                    } else {
-                        false
+    // `import.meta` is provided to each module factory by the runtime
+    fn fold_expr(&mut self, node: Expr) -> Expr {
+        match node {
+            // Each `import.meta.glob` pattern requires a module of the matching
+            // files, keyed by their path prefixed with the pattern's directory
+            Expr::Call(call) if is_import_meta_glob(&call.callee) => {
+                self.module_type = ModuleType::ESM;
+
+                let globs = parse_glob_args(&call.args).and_then(|(patterns, eager)| {
+                    patterns
+                        .iter()
+                        .map(|pattern| split_glob_pattern(pattern))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map(|globs| (globs, eager))
+                });
+                let (globs, eager) = match globs {
+                    Ok(globs) => globs,
+                    // Globs that can't be expanded throw when they run instead
+                    // of failing the build
+                    Err(message) => {
+                        self.invalid_globs.push((call.span, message.clone()));
+                        return create_runtime_call(
+                            "__invalidGlob__",
+                            vec![create_str_expr(&message.into())],
+                        );
                     }
+                };
+
+                let mut args = vec![];
+                for (dir, glob) in globs {
+                    let request: JsWord =
+                        format!("{}?glob={}{}", dir, glob, if eager { "&eager" } else { "" })
+                            .into();
+                    self.add_require(request.clone(), call.span);
+
+                    args.push(create_str_expr(&dir.into()));
+                    args.push(Expr::Call(create_require_call(&request)));
                 }
-                _ => false,
-            },
-            _ => false,
-        };
 
-        if is_require_call {
-            let request = match &*node.args[0].expr {
-                Expr::Lit(lit) => match lit {
-                    Lit::Str(request) => &request.value,
+                create_runtime_call("__importGlob__", args)
+            }
+            meta if is_import_meta(&meta) => {
+                // Only valid in ESM, even without any imports or exports
+                self.module_type = ModuleType::ESM;
+
+                Expr::Ident(Ident {
+                    span: meta.span(),
+                    optional: false,
+                    sym: "__importMeta__".into(),
+                })
+            }
+            // `require.resolve` is replaced by the id of the module it resolves to
+            Expr::Call(call) if is_callee(&call.callee, "require.resolve") => {
+                let call = call.fold_children_with(self);
+                let parts = match call.args.first() {
+                    Some(ExprOrSpread { spread: None, expr }) => get_request_parts(expr),
+                    _ => vec![None],
+                };
+
+                match parse_request_parts(&parts) {
+                    Some(RequestParts::Static(request)) => {
+                        self.add_require(request.clone(), call.span);
+                        create_str_expr(&request)
+                    }
                     _ => {
-                        panic!("Invalid syntax");
+                        self.dynamic_requires.push(call.span);
+
+                        Expr::Call(CallExpr {
+                            callee: ExprOrSuper::Expr(Box::new(create_ident_expr(
+                                &"__dynamicRequire__".into(),
+                            ))),
+                            ..call
+                        })
                     }
-                },
-                _ => {
-                    panic!("Complex require statements not implemented");
                 }
-            };
+            }
+            _ => node.fold_children_with(self),
+        }
+    }
 
-            self.dependencies.push(Dependency {
-                request: request.clone(),
-                import_type: ImportType::Require,
-            });
+    // Treat `new URL('./file', import.meta.url)` as a dependency on the file,
+    // or on a separate worker chunk when passed to `new Worker()`
+    fn fold_new_expr(&mut self, node: NewExpr) -> NewExpr {
+        let mut node = node;
+
+        if is_worker_constructor(&node) {
+            if let Some(Expr::New(url)) = node.args.as_mut().map(|args| &mut *args[0].expr) {
+                if let Some(request) = get_asset_url_request(url) {
+                    replace_url_request(url, format!("{}?worker", request).into());
+                    if self.module_workers {
+                        set_module_worker_type(&mut node);
+                    }
+                }
+            }
         }
 
-        node
+        if let Some(request) = get_asset_url_request(&node) {
+            replace_url_request(&mut node, format!("{}?url", request).into());
+        }
+
+        node.fold_children_with(self)
+    }
+
+    // CommonJS Support
+    fn fold_call_expr(&mut self, node: CallExpr) -> CallExpr {
+        let mut node = node.fold_children_with(self);
+
+        // `require.async` is the runtime's require of a module that may use
+        // top-level await, e.g. by lazy glob imports
+        if is_callee(&node.callee, "require.async") {
+            if let Some(ExprOrSpread { spread: None, expr }) = node.args.first_mut() {
+                if let Some(RequestParts::Static(request)) =
+                    parse_request_parts(&get_request_parts(expr))
+                {
+                    **expr = create_str_expr(&request);
+                    self.add_async_require(request, node.span);
+                }
+            }
+            return node;
+        }
+
+        // `import('./page')` resolves to the module's namespace once it's
+        // loaded, so it's bundled as an async require. Requests only known at
+        // runtime are left to the native `import()`.
+        if is_callee(&node.callee, "import") {
+            if let Some(ExprOrSpread { spread: None, expr }) = node.args.first() {
+                if let Some(RequestParts::Static(request)) =
+                    parse_request_parts(&get_request_parts(expr))
+                {
+                    self.add_async_require(request.clone(), node.span);
+                    return CallExpr {
+                        span: node.span,
+                        callee: ExprOrSuper::Expr(Box::new(Expr::Member(MemberExpr {
+                            span: DUMMY_SP,
+                            obj: ExprOrSuper::Expr(Box::new(create_ident_expr(&"require".into()))),
+                            prop: Box::new(create_ident_expr(&"async".into())),
+                            computed: false,
+                        }))),
+                        args: vec![
+                            ExprOrSpread {
+                                spread: None,
+                                expr: Box::new(create_str_expr(&request)),
+                            },
+                            ExprOrSpread {
+                                spread: None,
+                                expr: Box::new(create_str_expr(&"namespace".into())),
+                            },
+                        ],
+                        type_args: None,
+                    };
+                }
+            }
+            return node;
+        }
+
+        if !is_callee(&node.callee, "require") {
+            return node;
+        }
+
+        let parts = match node.args.first() {
+            Some(ExprOrSpread { spread: None, expr }) => get_request_parts(expr),
+            _ => vec![None],
+        };
+
+        match parse_request_parts(&parts) {
+            Some(RequestParts::Static(request)) => {
+                *node.args[0].expr = create_str_expr(&request);
+                self.add_require(request, node.span);
+                node
+            }
+            // `require('./locales/' + lang)` requires from a context module of
+            // every matching file, called with the request relative to its directory
+            Some(RequestParts::Context { dir, glob }) => {
+                let request: JsWord = format!("{}?context={}", dir, glob).into();
+                replace_request_prefix(&mut node.args[0].expr, dir.len());
+                self.add_require(request.clone(), node.span);
+
+                CallExpr {
+                    span: node.span,
+                    callee: ExprOrSuper::Expr(Box::new(Expr::Call(create_require_call(&request)))),
+                    args: node.args,
+                    type_args: None,
+                }
+            }
+            // Truly dynamic requires throw when they run instead of failing the build
+            None => {
+                self.dynamic_requires.push(node.span);
+
+                CallExpr {
+                    callee: ExprOrSuper::Expr(Box::new(create_ident_expr(
+                        &"__dynamicRequire__".into(),
+                    ))),
+                    ..node
+                }
+            }
+        }
     }
 }
 
 impl RuntimeImportMapper {
-    fn create_runtime_require(&self, dependency: &Dependency) -> ModuleItem {
-        let mut is_default_import = false;
+    fn add_require(&mut self, request: JsWord, span: Span) {
+        self.dependencies.push(Dependency {
+            request,
+            import_type: ImportType::Require,
+            span,
+            is_async: false,
+            loc: None,
+        });
+    }
+
+    /// Adds a require that's loaded on demand, e.g. by calling a lazy glob import
+    fn add_async_require(&mut self, request: JsWord, span: Span) {
+        self.dependencies.push(Dependency {
+            request,
+            import_type: ImportType::Require,
+            span,
+            is_async: true,
+            loc: None,
+        });
+    }
+
+    fn add_reexport_namespace(&mut self, src: &Str) -> JsWord {
+        let namespace_local: JsWord =
+            format!("namespace_{}", strip_invalid_chars(&src.value)).into();
+        let is_required = self.dependencies.iter().any(|dep| match &dep.import_type {
+            ImportType::Namespace(local) => *local == namespace_local,
+            _ => false,
+        });
+
+        if !is_required {
+            self.dependencies.push(Dependency {
+                request: src.value.clone(),
+                import_type: ImportType::Namespace(namespace_local.clone()),
+                span: src.span,
+                is_async: false,
+                loc: None,
+            });
+        }
+
+        namespace_local
+    }
+
+    /// Creates the runtime require for an import. Default and namespace
+    /// imports pass how CommonJS exports should be interop'd into them.
+    fn create_runtime_require(&self, index: usize, dependency: &Dependency) -> ModuleItem {
+        let mut interop = None;
         let decl_name = match &dependency.import_type {
-            ImportType::Namespace(local) => Pat::Ident(BindingIdent::from(Ident {
-                sym: local.into(),
+            ImportType::Namespace(local) => {
+                interop = Some("namespace");
+
+                Pat::Ident(BindingIdent::from(Ident {
+                    sym: local.clone(),
+                    span: DUMMY_SP,
+                    optional: false,
+                }))
+            }
+            // Named and default imports read through the module's exports when
+            // used, so they stay live bindings
+            ImportType::Named(_) => Pat::Ident(BindingIdent::from(Ident {
+                sym: import_local(index),
                 span: DUMMY_SP,
                 optional: false,
             })),
-            ImportType::Named(locals) => Pat::Object(ObjectPat {
-                span: DUMMY_SP,
-                optional: false,
-                type_ann: None,
-                props: locals
-                    .iter()
-                    .map(|named_import| {
-                        if named_import.import_name == named_import.local {
-                            ObjectPatProp::Assign(AssignPatProp {
-                                span: DUMMY_SP,
-                                key: Ident {
-                                    sym: named_import.local.clone(),
-                                    span: DUMMY_SP,
-                                    optional: false,
-                                },
-                                value: None,
-                            })
-                        } else {
-                            ObjectPatProp::KeyValue(KeyValuePatProp {
-                                key: PropName::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    optional: false,
-                                    sym: named_import.import_name.clone(),
-                                }),
-                                value: Box::from(Pat::Ident(BindingIdent::from(Ident {
-                                    sym: named_import.local.clone(),
-                                    span: DUMMY_SP,
-                                    optional: false,
-                                }))),
-                            })
-                        }
-                    })
-                    .collect(),
-            }),
-            ImportType::Default(local) => {
-                is_default_import = true;
+            ImportType::Default(_) => {
+                interop = Some("default");
 
-                Pat::Object(ObjectPat {
+                Pat::Ident(BindingIdent::from(Ident {
+                    sym: import_local(index),
                     span: DUMMY_SP,
                     optional: false,
-                    type_ann: None,
-                    props: vec![ObjectPatProp::KeyValue(KeyValuePatProp {
-                        key: PropName::Ident(Ident {
-                            span: DUMMY_SP,
-                            optional: false,
-                            sym: "default".into(),
-                        }),
-                        value: Box::from(Pat::Ident(BindingIdent::from(Ident {
-                            sym: local.clone(),
-                            span: DUMMY_SP,
-                            optional: false,
-                        }))),
-                    })],
-                })
+                }))
             }
             ImportType::SideEffect => {
                 panic!("NOT IMPLEMENTED: Side effect imports");
@@ -386,24 +621,18 @@ impl RuntimeImportMapper {
                         span: DUMMY_SP,
                         optional: false,
                     }))),
-                    args: vec![
-                        ExprOrSpread {
+                    args: std::iter::once(dependency.request.clone())
+                        .chain(interop.map(JsWord::from))
+                        .map(|value| ExprOrSpread {
                             expr: Box::from(Expr::Lit(Lit::Str(Str {
-                                value: dependency.request.clone().into(),
+                                value,
                                 span: DUMMY_SP,
                                 has_escape: true,
                                 kind: StrKind::Synthesized,
                             }))),
                             spread: None,
-                        },
-                        ExprOrSpread {
-                            expr: Box::from(Expr::Lit(Lit::Bool(Bool {
-                                span: DUMMY_SP,
-                                value: is_default_import,
-                            }))),
-                            spread: None,
-                        },
-                    ],
+                        })
+                        .collect(),
                     span: DUMMY_SP,
                     type_args: None,
                 }))),
@@ -414,27 +643,855 @@ impl RuntimeImportMapper {
     }
 }
 
-fn create_runtime_export(name: &JsWord, value: &Box<Expr>) -> Box<Expr> {
-    Box::new(Expr::Assign(AssignExpr {
+/// Rewrites each use of an imported binding into a read from its module's
+/// exports, e.g. `count` into `__import_0__.count`. Bindings are matched by
+/// syntax context so local variables shadowing an import are left alone.
+struct ImportBindingRewriter<'a> {
+    bindings: &'a HashMap<Id, (JsWord, JsWord)>,
+}
+
+impl ImportBindingRewriter<'_> {
+    fn get_member_expr(&self, ident: &Ident) -> Option<Expr> {
+        let (namespace_local, import_name) = self.bindings.get(&ident.to_id())?;
+
+        Some(Expr::Member(MemberExpr {
+            span: ident.span,
+            obj: ExprOrSuper::Expr(Box::new(create_ident_expr(namespace_local))),
+            prop: Box::new(Expr::Ident(Ident {
+                span: DUMMY_SP,
+                optional: false,
+                sym: import_name.clone(),
+            })),
+            computed: false,
+        }))
+    }
+}
+
+impl VisitMut for ImportBindingRewriter<'_> {
+    fn visit_mut_expr(&mut self, node: &mut Expr) {
+        if let Expr::Ident(ident) = node {
+            if let Some(member_expr) = self.get_member_expr(ident) {
+                *node = member_expr;
+            }
+            return;
+        }
+        node.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_member_expr(&mut self, node: &mut MemberExpr) {
+        node.obj.visit_mut_with(self);
+        // Non-computed props are names rather than references
+        if node.computed {
+            node.prop.visit_mut_with(self);
+        }
+    }
+
+    fn visit_mut_prop(&mut self, node: &mut Prop) {
+        if let Prop::Shorthand(ident) = node {
+            if let Some(member_expr) = self.get_member_expr(ident) {
+                *node = Prop::KeyValue(KeyValueProp {
+                    key: PropName::Ident(Ident {
+                        span: DUMMY_SP,
+                        optional: false,
+                        sym: ident.sym.clone(),
+                    }),
+                    value: Box::new(member_expr),
+                });
+            }
+            return;
+        }
+        node.visit_mut_children_with(self);
+    }
+
+    // Components are referenced by JSX element names, e.g. `<Button />`
+    fn visit_mut_jsx_element_name(&mut self, node: &mut JSXElementName) {
+        match node {
+            JSXElementName::Ident(ident) => {
+                if let Some((namespace_local, import_name)) = self.bindings.get(&ident.to_id()) {
+                    *node = JSXElementName::JSXMemberExpr(JSXMemberExpr {
+                        obj: JSXObject::Ident(Ident {
+                            span: ident.span,
+                            optional: false,
+                            sym: namespace_local.clone(),
+                        }),
+                        prop: Ident {
+                            span: DUMMY_SP,
+                            optional: false,
+                            sym: import_name.clone(),
+                        },
+                    });
+                }
+            }
+            _ => node.visit_mut_children_with(self),
+        }
+    }
+
+    fn visit_mut_jsx_member_expr(&mut self, node: &mut JSXMemberExpr) {
+        // Only the object is a reference, e.g. `ui` in `<ui.Button />`
+        if let JSXObject::Ident(ident) = &node.obj {
+            if let Some((namespace_local, import_name)) = self.bindings.get(&ident.to_id()) {
+                node.obj = JSXObject::JSXMemberExpr(Box::new(JSXMemberExpr {
+                    obj: JSXObject::Ident(Ident {
+                        span: ident.span,
+                        optional: false,
+                        sym: namespace_local.clone(),
+                    }),
+                    prop: Ident {
+                        span: DUMMY_SP,
+                        optional: false,
+                        sym: import_name.clone(),
+                    },
+                }));
+                return;
+            }
+        }
+        node.obj.visit_mut_with(self);
+    }
+}
+
+/// Wraps each assignment to an exported binding in a call to the runtime's
+/// `__exportUpdated__`, e.g. `count++` into `__exportUpdated__(count++)`, so
+/// copies of the module's exports can be updated.
+struct ExportUpdateNotifier<'a> {
+    bindings: &'a HashSet<Id>,
+}
+
+impl ExportUpdateNotifier<'_> {
+    fn assigns_binding(&self, node: &Expr) -> bool {
+        match node {
+            Expr::Assign(assign) => {
+                let ids: Vec<Id> = match &assign.left {
+                    PatOrExpr::Pat(pat) => find_ids(pat),
+                    PatOrExpr::Expr(expr) => match &**expr {
+                        Expr::Ident(ident) => vec![ident.to_id()],
+                        _ => vec![],
+                    },
+                };
+                ids.iter().any(|id| self.bindings.contains(id))
+            }
+            Expr::Update(update) => match &*update.arg {
+                Expr::Ident(ident) => self.bindings.contains(&ident.to_id()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl VisitMut for ExportUpdateNotifier<'_> {
+    fn visit_mut_expr(&mut self, node: &mut Expr) {
+        node.visit_mut_children_with(self);
+
+        if self.assigns_binding(node) {
+            let assignment = std::mem::replace(node, Expr::Invalid(Invalid { span: DUMMY_SP }));
+            *node = create_runtime_call("__exportUpdated__", vec![assignment]);
+        }
+    }
+}
+
+/// The variable holding the exports required by the dependency at `index`
+fn import_local(index: usize) -> JsWord {
+    format!("__import_{}__", index).into()
+}
+
+/// Returns the bindings created at runtime by an exported declaration.
+/// Type-only and ambient declarations are stripped later so export nothing.
+fn get_exported_decl_idents(decl: &Decl) -> Vec<Ident> {
+    match decl {
+        Decl::Var(var_decl) if !var_decl.declare => find_ids(&var_decl.decls),
+        Decl::Fn(fn_decl) if !fn_decl.declare => vec![fn_decl.ident.clone()],
+        Decl::Class(class_decl) if !class_decl.declare => vec![class_decl.ident.clone()],
+        Decl::TsEnum(enum_decl) if !enum_decl.declare => vec![enum_decl.id.clone()],
+        Decl::TsModule(TsModuleDecl {
+            declare: false,
+            id: TsModuleName::Ident(ident),
+            ..
+        }) => vec![ident.clone()],
+        _ => vec![],
+    }
+}
+
+/// Returns true if `callee` is `name`, e.g. `require` or `require.resolve`
+fn is_callee(callee: &ExprOrSuper, name: &str) -> bool {
+    let callee = match callee {
+        ExprOrSuper::Expr(callee) => &**callee,
+        _ => return false,
+    };
+
+    match (callee, name.split_once('.')) {
+        (Expr::Ident(ident), None) => &*ident.sym == name,
+        (
+            Expr::Member(MemberExpr {
+                obj: ExprOrSuper::Expr(obj),
+                prop,
+                computed: false,
+                ..
+            }),
+            Some((obj_name, prop_name)),
+        ) => {
+            matches!(&**obj, Expr::Ident(ident) if &*ident.sym == obj_name)
+                && matches!(&**prop, Expr::Ident(ident) if &*ident.sym == prop_name)
+        }
+        _ => false,
+    }
+}
+
+fn is_import_meta(node: &Expr) -> bool {
+    matches!(node, Expr::MetaProp(meta) if &*meta.meta.sym == "import" && &*meta.prop.sym == "meta")
+}
+
+/// Returns true if `node` is `import.meta.[name]`, e.g. `import.meta.url`
+fn is_import_meta_prop(node: &Expr, name: &str) -> bool {
+    match node {
+        Expr::Member(MemberExpr {
+            obj: ExprOrSuper::Expr(obj),
+            prop,
+            computed: false,
+            ..
+        }) => is_import_meta(obj) && matches!(&**prop, Expr::Ident(ident) if &*ident.sym == name),
+        _ => false,
+    }
+}
+
+fn is_import_meta_glob(callee: &ExprOrSuper) -> bool {
+    matches!(callee, ExprOrSuper::Expr(callee) if is_import_meta_prop(callee, "glob"))
+}
+
+/// Returns the patterns of `import.meta.glob(pattern | patterns, { eager })`,
+/// which must be literals so they can be expanded at build time
+fn parse_glob_args(args: &[ExprOrSpread]) -> Result<(Vec<JsWord>, bool), String> {
+    let not_literal = || "import.meta.glob patterns must be string literals".to_string();
+    let patterns = match args.first().map(|arg| &*arg.expr) {
+        Some(Expr::Lit(Lit::Str(pattern))) => vec![pattern.value.clone()],
+        Some(Expr::Array(array)) => array
+            .elems
+            .iter()
+            .map(|elem| match elem.as_ref().map(|elem| &*elem.expr) {
+                Some(Expr::Lit(Lit::Str(pattern))) => Ok(pattern.value.clone()),
+                _ => Err(not_literal()),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(not_literal()),
+    };
+
+    let eager = match args.get(1).map(|arg| &*arg.expr) {
+        Some(Expr::Object(options)) => options.props.iter().any(|prop| match prop {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(KeyValueProp {
+                    key: PropName::Ident(key),
+                    value,
+                }) if &*key.sym == "eager" => {
+                    matches!(&**value, Expr::Lit(Lit::Bool(Bool { value: true, .. })))
+                }
+                _ => false,
+            },
+            _ => false,
+        }),
+        _ => false,
+    };
+
+    Ok((patterns, eager))
+}
+
+/// Splits a glob pattern into the relative directory before its first
+/// wildcard and the glob matched against files within it, e.g. `./pages/`
+/// and `**/*.tsx`
+fn split_glob_pattern(pattern: &str) -> Result<(String, String), String> {
+    if !pattern.starts_with("./") && !pattern.starts_with("../") {
+        return Err(format!(
+            "import.meta.glob patterns must be relative, e.g. './{}'",
+            pattern
+        ));
+    }
+    // Kept out of module ids, which are emitted into the chunk as they are
+    if pattern.contains(['?', '&', '"', '\\']) {
+        return Err(format!("Unsupported import.meta.glob pattern {}", pattern));
+    }
+
+    let static_len = pattern.find('*').unwrap_or(pattern.len());
+    // Relative patterns always have a `/` before any wildcard
+    let dir_len = pattern[..static_len].rfind('/').unwrap() + 1;
+    Ok((
+        pattern[..dir_len].to_string(),
+        pattern[dir_len..].to_string(),
+    ))
+}
+
+enum RequestParts {
+    Static(JsWord),
+    /// Every file in `dir` matching `glob`, where `*` matches any characters
+    Context {
+        dir: String,
+        glob: String,
+    },
+}
+
+/// Splits a request built from string concatenation or a template literal into
+/// its static parts, with `None` for each dynamic part.
+fn get_request_parts(expr: &Expr) -> Vec<Option<JsWord>> {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => vec![Some(str.value.clone())],
+        Expr::Paren(paren) => get_request_parts(&paren.expr),
+        Expr::Bin(BinExpr {
+            op: BinaryOp::Add,
+            left,
+            right,
+            ..
+        }) => {
+            let mut parts = get_request_parts(left);
+            parts.extend(get_request_parts(right));
+            parts
+        }
+        Expr::Tpl(tpl) => {
+            let mut parts = vec![];
+            for (index, quasi) in tpl.quasis.iter().enumerate() {
+                let cooked = quasi.cooked.as_ref().unwrap_or(&quasi.raw);
+                parts.push(Some(cooked.value.clone()));
+                if index < tpl.exprs.len() {
+                    parts.extend(get_request_parts(&tpl.exprs[index]));
+                }
+            }
+            parts
+        }
+        _ => vec![None],
+    }
+}
+
+/// Requests with dynamic parts become a context when they start with a
+/// relative directory, otherwise they can't be bundled.
+fn parse_request_parts(parts: &[Option<JsWord>]) -> Option<RequestParts> {
+    if parts.iter().all(Option::is_some) {
+        let request: String = parts.iter().flatten().map(|part| &**part).collect();
+        return Some(RequestParts::Static(request.into()));
+    }
+
+    let prefix = parts.first()?.as_ref()?;
+    if !prefix.starts_with("./") && !prefix.starts_with("../") {
+        return None;
+    }
+
+    let dir_len = prefix.rfind('/')? + 1;
+    let mut glob = prefix[dir_len..].to_string();
+    for part in &parts[1..] {
+        match part {
+            Some(part) => glob.push_str(part),
+            None if glob.ends_with('*') => {}
+            None => glob.push('*'),
+        }
+    }
+
+    // Kept out of module ids, which are emitted into the chunk as they are
+    if glob.contains(['?', '"', '\\']) || prefix[..dir_len].contains('"') {
+        return None;
+    }
+
+    Some(RequestParts::Context {
+        dir: prefix[..dir_len].to_string(),
+        glob,
+    })
+}
+
+/// Replaces the first `len` characters of a request, its context directory,
+/// with `./` so the request matches the keys of the context module.
+fn replace_request_prefix(expr: &mut Expr, len: usize) {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => {
+            *str = Str {
+                span: DUMMY_SP,
+                value: format!("./{}", &str.value[len..]).into(),
+                has_escape: false,
+                kind: StrKind::Synthesized,
+            };
+        }
+        Expr::Paren(paren) => replace_request_prefix(&mut paren.expr, len),
+        Expr::Bin(bin) => replace_request_prefix(&mut bin.left, len),
+        Expr::Tpl(tpl) => {
+            let quasi = &mut tpl.quasis[0];
+            if let Some(cooked) = &mut quasi.cooked {
+                cooked.value = format!("./{}", &cooked.value[len..]).into();
+            }
+            // The raw value is only escaped differently after the directory
+            let raw_len = quasi.raw.value.rfind('/').map_or(0, |index| index + 1);
+            quasi.raw.value = format!("./{}", &quasi.raw.value[raw_len..]).into();
+        }
+        _ => {}
+    }
+}
+
+fn create_str_expr(value: &JsWord) -> Expr {
+    Expr::Lit(Lit::Str(Str {
         span: DUMMY_SP,
+        value: value.clone(),
+        has_escape: false,
+        kind: StrKind::Synthesized,
+    }))
+}
 
-        op: AssignOp::Assign,
+pub fn create_ident_expr(sym: &JsWord) -> Expr {
+    Expr::Ident(Ident {
+        span: DUMMY_SP,
+        optional: false,
+        sym: sym.clone(),
+    })
+}
 
-        left: PatOrExpr::Expr(Box::new(Expr::Member(MemberExpr {
+fn create_member_expr(obj: &JsWord, prop: &JsWord) -> Expr {
+    Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: ExprOrSuper::Expr(Box::new(create_ident_expr(obj))),
+        prop: Box::new(Expr::Lit(Lit::Str(Str {
             span: DUMMY_SP,
-            obj: ExprOrSuper::Expr(Box::new(Expr::Ident(Ident {
+            value: prop.clone(),
+            has_escape: false,
+            kind: StrKind::Synthesized,
+        }))),
+        computed: true,
+    })
+}
+
+fn create_runtime_call(callee: &str, args: Vec<Expr>) -> Expr {
+    Expr::Call(CallExpr {
+        span: DUMMY_SP,
+        type_args: None,
+        callee: ExprOrSuper::Expr(Box::new(create_ident_expr(&callee.into()))),
+        args: args
+            .into_iter()
+            .map(|arg| ExprOrSpread {
+                spread: None,
+                expr: Box::new(arg),
+            })
+            .collect(),
+    })
+}
+
+pub fn create_var_decl(name: &JsWord, init: Box<Expr>) -> ModuleItem {
+    ModuleItem::Stmt(Stmt::Decl(Decl::Var(VarDecl {
+        span: DUMMY_SP,
+        kind: VarDeclKind::Var,
+        declare: false,
+        decls: vec![VarDeclarator {
+            span: DUMMY_SP,
+            name: Pat::Ident(BindingIdent::from(Ident {
                 span: DUMMY_SP,
-                sym: "exports".into(),
                 optional: false,
+                sym: name.clone(),
+            })),
+            init: Some(init),
+            definite: false,
+        }],
+    })))
+}
+
+/// Creates `__export__(exports, { name: () => value })`, defining each export
+/// as a getter so importers see the binding's current value.
+fn create_export_getters(export_getters: Vec<(JsWord, Expr)>) -> ModuleItem {
+    let getters = Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props: export_getters
+            .into_iter()
+            .map(|(name, value)| {
+                PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                    key: PropName::Str(Str {
+                        span: DUMMY_SP,
+                        value: name,
+                        has_escape: false,
+                        kind: StrKind::Synthesized,
+                    }),
+                    value: Box::new(Expr::Arrow(ArrowExpr {
+                        span: DUMMY_SP,
+                        params: vec![],
+                        body: BlockStmtOrExpr::Expr(Box::new(value)),
+                        is_async: false,
+                        is_generator: false,
+                        type_params: None,
+                        return_type: None,
+                    })),
+                })))
+            })
+            .collect(),
+    });
+
+    ModuleItem::Stmt(Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(create_runtime_call(
+            "__export__",
+            vec![create_ident_expr(&"exports".into()), getters],
+        )),
+    }))
+}
+
+pub fn create_require_call(request: &JsWord) -> CallExpr {
+    CallExpr {
+        span: DUMMY_SP,
+        type_args: None,
+        callee: ExprOrSuper::Expr(Box::new(Expr::Ident(Ident {
+            span: DUMMY_SP,
+            optional: false,
+            sym: "require".into(),
+        }))),
+        args: vec![ExprOrSpread {
+            spread: None,
+            expr: Box::new(Expr::Lit(Lit::Str(Str {
+                span: DUMMY_SP,
+                value: request.clone(),
+                has_escape: false,
+                kind: StrKind::Synthesized,
             }))),
+        }],
+    }
+}
+
+/// Replaces the file of `new URL('./file', import.meta.url)` with the default
+/// export of `request`. The require call is collected as a dependency when
+/// folding children.
+fn replace_url_request(node: &mut NewExpr, request: JsWord) {
+    if let Some(args) = &mut node.args {
+        *args[0].expr = Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: ExprOrSuper::Expr(Box::new(Expr::Call(create_require_call(&request)))),
             prop: Box::new(Expr::Ident(Ident {
                 span: DUMMY_SP,
-                sym: name.into(),
                 optional: false,
+                sym: "default".into(),
             })),
             computed: false,
-        }))),
+        });
+    }
+}
 
-        right: value.clone(),
-    }))
+fn is_worker_constructor(node: &NewExpr) -> bool {
+    let is_worker = matches!(&*node.callee, Expr::Ident(ident) if &*ident.sym == "Worker" || &*ident.sym == "SharedWorker");
+    is_worker && node.args.as_ref().is_some_and(|args| !args.is_empty())
+}
+
+/// Adds `type: 'module'` to the options of `new Worker()`, unless a type is
+/// already set or the options aren't an object literal
+fn set_module_worker_type(node: &mut NewExpr) {
+    let type_prop = PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key: PropName::Ident(Ident {
+            span: DUMMY_SP,
+            optional: false,
+            sym: "type".into(),
+        }),
+        value: Box::new(create_str_expr(&"module".into())),
+    })));
+    let args = match node.args.as_mut() {
+        Some(args) => args,
+        None => return,
+    };
+
+    match args.get_mut(1).map(|arg| &mut *arg.expr) {
+        None => args.push(ExprOrSpread {
+            spread: None,
+            expr: Box::new(Expr::Object(ObjectLit {
+                span: DUMMY_SP,
+                props: vec![type_prop],
+            })),
+        }),
+        Some(Expr::Object(options)) => {
+            let has_type = options.props.iter().any(|prop| match prop {
+                PropOrSpread::Prop(prop) => match &**prop {
+                    Prop::KeyValue(KeyValueProp {
+                        key: PropName::Ident(Ident { sym, .. }),
+                        ..
+                    }) => &**sym == "type",
+                    Prop::KeyValue(KeyValueProp {
+                        key: PropName::Str(Str { value, .. }),
+                        ..
+                    }) => &**value == "type",
+                    _ => false,
+                },
+                PropOrSpread::Spread(_) => false,
+            });
+            if !has_type {
+                options.props.push(type_prop);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the relative request of `new URL('./file', import.meta.url)`
+fn get_asset_url_request(node: &NewExpr) -> Option<JsWord> {
+    match &*node.callee {
+        Expr::Ident(ident) if &*ident.sym == "URL" => {}
+        _ => return None,
+    }
+
+    let args = node.args.as_ref()?;
+    if args.len() != 2 {
+        return None;
+    }
+
+    let is_import_meta_url = is_import_meta_prop(&args[1].expr, "url");
+
+    match &*args[0].expr {
+        Expr::Lit(Lit::Str(request))
+            if is_import_meta_url
+                && (request.value.starts_with("./") || request.value.starts_with("../"))
+                && !request.value.contains('?') =>
+        {
+            Some(request.value.clone())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_loader::emit;
+    use crate::parser::parse;
+    use swc_common::sync::Lrc;
+    use swc_common::{Globals, Mark, SourceMap, GLOBALS};
+    use swc_ecmascript::transforms::resolver::ts_resolver;
+
+    fn transform(code: &str) -> String {
+        transform_with(code, false)
+    }
+
+    fn transform_with(code: &str, module_workers: bool) -> String {
+        GLOBALS.set(&Globals::new(), || {
+            let source_map = Lrc::new(SourceMap::default());
+            let (module, comments) = parse(code, "test.js", &source_map).unwrap();
+            let module = module.fold_with(&mut ts_resolver(Mark::fresh(Mark::root())));
+            let (module, ..) = runtime_imports(module, module_workers);
+            String::from_utf8(emit(&module, source_map, comments).unwrap()).unwrap()
+        })
+    }
+
+    #[test]
+    fn reads_import_meta_from_the_runtime_object() {
+        let code = transform("console.log(import.meta.url, import.meta.env.MODE);");
+        assert!(code.contains("console.log(__importMeta__.url, __importMeta__.env.MODE);"));
+    }
+
+    #[test]
+    fn requires_files_referenced_by_import_meta_urls() {
+        let code = transform(
+            "new URL('./logo.png', import.meta.url);\n\
+             new URL('https://example.com', import.meta.url);",
+        );
+        assert!(code.contains("new URL(require(\"./logo.png?url\").default, __importMeta__.url);"));
+        assert!(code.contains("new URL('https://example.com', __importMeta__.url);"));
+    }
+    #[test]
+    fn reads_imported_bindings_through_exports() {
+        let code = transform(
+            "import def, { count, inc as increment } from './counter';\n\
+             increment();\n\
+             console.log(def, { count });",
+        );
+        assert!(code.contains("var __import_0__ = require(\"./counter\", \"default\");"));
+        assert!(code.contains("var __import_1__ = require(\"./counter\");"));
+        assert!(code.contains("__import_1__.inc();"));
+        assert!(code
+            .contains("console.log(__import_0__.default, {\n    count: __import_1__.count\n});"));
+    }
+
+    #[test]
+    fn passes_how_default_and_namespace_imports_interop() {
+        let code = transform(
+            "import * as path from 'path';\n\
+             import React from 'react';\n\
+             import { useState } from 'react';\n\
+             console.log(path, React, useState);",
+        );
+        assert!(code.contains("var path = require(\"path\", \"namespace\");"));
+        assert!(code.contains("var __import_1__ = require(\"react\", \"default\");"));
+        assert!(code.contains("var __import_2__ = require(\"react\");"));
+    }
+
+    #[test]
+    fn requires_concatenated_requests_from_a_context() {
+        let code = transform(
+            "require('./locales/' + lang + '.json');\n\
+             require(`./pages/${page}`);",
+        );
+        assert!(code.contains("require(\"./locales/?context=*.json\")(\"./\" + lang + '.json');"));
+        assert!(code.contains("require(\"./pages/?context=*\")(`./${page}`);"));
+    }
+
+    #[test]
+    fn replaces_require_resolve_with_the_request() {
+        let code = transform("var id = require.resolve('./' + 'file');");
+        assert!(code.contains("var id = \"./file\";"));
+    }
+
+    #[test]
+    fn throws_on_requires_only_known_at_runtime() {
+        let (module, dependencies, _, _, dynamic_requires, _) =
+            GLOBALS.set(&Globals::new(), || {
+                let source_map = Lrc::new(SourceMap::default());
+                let (module, _) = parse(
+                    "require(name);\nrequire('pkg/' + name);\nrequire.resolve(name);",
+                    "test.js",
+                    &source_map,
+                )
+                .unwrap();
+                runtime_imports(module, false)
+            });
+        assert_eq!(dynamic_requires.len(), 3);
+        assert!(dependencies.is_empty());
+        assert_eq!(module.body.len(), 3);
+    }
+
+    #[test]
+    fn requires_a_module_for_each_glob_pattern() {
+        let code = transform(
+            "const pages = import.meta.glob('./pages/**/*.tsx');\n\
+             const posts = import.meta.glob(['./posts/*.md', '../drafts/*.md'], { eager: true });",
+        );
+        assert!(code.contains(
+            "const pages = __importGlob__(\"./pages/\", require(\"./pages/?glob=**/*.tsx\"));"
+        ));
+        assert!(code.contains("require(\"./posts/?glob=*.md&eager\")"));
+        assert!(code.contains("require(\"../drafts/?glob=*.md&eager\")"));
+    }
+
+    #[test]
+    fn marks_lazy_glob_imports_as_async() {
+        let (_, dependencies, ..) = GLOBALS.set(&Globals::new(), || {
+            let source_map = Lrc::new(SourceMap::default());
+            let (module, _) = parse(
+                "module.exports = { post: () => require.async('./posts/post.md', 'namespace') };",
+                "test.js",
+                &source_map,
+            )
+            .unwrap();
+            runtime_imports(module, false)
+        });
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(&*dependencies[0].request, "./posts/post.md");
+        assert!(dependencies[0].is_async);
+    }
+
+    #[test]
+    fn throws_on_globs_that_cant_be_expanded() {
+        let (module, dependencies, .., invalid_globs) = GLOBALS.set(&Globals::new(), || {
+            let source_map = Lrc::new(SourceMap::default());
+            let (module, _) = parse(
+                "import.meta.glob(pattern);\n\
+                 import.meta.glob(['./pages/*.tsx', pattern]);\n\
+                 import.meta.glob('pages/*.tsx');\n\
+                 import.meta.glob('./pages/*.tsx?raw');\n\
+                 import.meta.glob('./pages/\"*\".tsx');",
+                "test.js",
+                &source_map,
+            )
+            .unwrap();
+            runtime_imports(module, false)
+        });
+        let messages: Vec<&str> = invalid_globs
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "import.meta.glob patterns must be string literals",
+                "import.meta.glob patterns must be string literals",
+                "import.meta.glob patterns must be relative, e.g. './pages/*.tsx'",
+                "Unsupported import.meta.glob pattern ./pages/*.tsx?raw",
+                "Unsupported import.meta.glob pattern ./pages/\"*\".tsx",
+            ]
+        );
+        assert!(dependencies.is_empty());
+        assert_eq!(module.body.len(), 5);
+    }
+
+    #[test]
+    fn requires_worker_chunks_for_worker_urls() {
+        let code = transform(
+            "new Worker(new URL('./parser.worker.ts', import.meta.url));\n\
+             new SharedWorker(new URL('./shared.ts', import.meta.url), { name: 'shared' });\n\
+             new MyWorker(new URL('./custom.ts', import.meta.url));\n\
+             new Worker(url);",
+        );
+        assert!(code.contains(
+            "new Worker(new URL(require(\"./parser.worker.ts?worker\").default, __importMeta__.url));"
+        ));
+        assert!(code.contains("new SharedWorker(new URL(require(\"./shared.ts?worker\").default"));
+        // Only `Worker` and `SharedWorker` are known to start workers
+        assert!(code.contains(
+            "new MyWorker(new URL(require(\"./custom.ts?url\").default, __importMeta__.url));"
+        ));
+        assert!(code.contains("new Worker(url);"));
+    }
+
+    #[test]
+    fn starts_module_workers_for_es_module_chunks() {
+        let code = transform_with(
+            "new Worker(new URL('./a.ts', import.meta.url));\n\
+             new Worker(new URL('./b.ts', import.meta.url), { name: 'b' });\n\
+             new Worker(new URL('./c.ts', import.meta.url), { type: 'classic' });",
+            true,
+        );
+        assert!(code.contains("__importMeta__.url), {\n    type: \"module\"\n});"));
+        assert!(code.contains("name: 'b',\n    type: \"module\"\n});"));
+        assert!(code.contains("{\n    type: 'classic'\n});"));
+    }
+
+    #[test]
+    fn leaves_locals_shadowing_imports_alone() {
+        let code = transform(
+            "import { count } from './counter';\n\
+             function shadow(count) { return count + 1; }\n\
+             shadow(count);",
+        );
+        assert!(code.contains("return count + 1;"));
+        assert!(code.contains("shadow(__import_0__.count);"));
+    }
+
+    #[test]
+    fn defines_exports_as_getters() {
+        let code = transform("export let count = 0;\nexport function inc() { count++; }");
+        assert!(code.starts_with("__export__(exports, {\n    \"count\": ()=>count\n    ,"));
+        assert!(code.contains("let count = 0;"));
+    }
+
+    #[test]
+    fn exports_every_binding_of_a_declaration() {
+        let code = transform(
+            "export const { a, b: [c] } = obj;\n\
+             export class Store {}\n\
+             export enum Color { Red }\n\
+             export declare const ambient: number;",
+        );
+        assert!(code.contains("\"a\": ()=>a"));
+        assert!(code.contains("\"c\": ()=>c"));
+        assert!(code.contains("\"Store\": ()=>Store"));
+        assert!(code.contains("\"Color\": ()=>Color"));
+        assert!(!code.contains("\"ambient\""));
+    }
+
+    #[test]
+    fn names_anonymous_default_declarations() {
+        let code = transform("export default function () {}");
+        assert!(code.contains("\"default\": ()=>__default__"));
+        assert!(code.contains("function __default__() {\n}"));
+    }
+
+    #[test]
+    fn loads_dynamic_imports_as_async_requires() {
+        let (code, dependencies) = GLOBALS.set(&Globals::new(), || {
+            let source_map = Lrc::new(SourceMap::default());
+            let (module, comments) = parse(
+                "import('./pages/home.js').then(render);\n\
+                 import(`./pages/${name}.js`);",
+                "test.js",
+                &source_map,
+            )
+            .unwrap();
+            let (module, dependencies, ..) = runtime_imports(module, false);
+            let code = String::from_utf8(emit(&module, source_map, comments).unwrap()).unwrap();
+            (code, dependencies)
+        });
+        assert!(code.contains("require.async(\"./pages/home.js\", \"namespace\").then(render);"));
+        // Requests only known at runtime are left to the native `import()`
+        assert!(code.contains("import(`./pages/${name}.js`);"));
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(&*dependencies[0].request, "./pages/home.js");
+        assert!(dependencies[0].is_async);
+    }
 }
@@ -0,0 +1,75 @@
+use swc_common::DUMMY_SP;
+use swc_ecmascript::ast::*;
+use swc_ecmascript::visit::{Node, Visit, VisitWith};
+
+/// Returns true if the module uses `await` or `for await` outside of any
+/// function, so it has to be evaluated asynchronously.
+pub fn has_top_level_await(module: &Module) -> bool {
+    let mut finder = TopLevelAwaitFinder { found: false };
+    module.visit_with(&Invalid { span: DUMMY_SP } as _, &mut finder);
+    finder.found
+}
+
+struct TopLevelAwaitFinder {
+    found: bool,
+}
+
+impl Visit for TopLevelAwaitFinder {
+    fn visit_await_expr(&mut self, _node: &AwaitExpr, _parent: &dyn Node) {
+        self.found = true;
+    }
+
+    fn visit_for_of_stmt(&mut self, node: &ForOfStmt, _parent: &dyn Node) {
+        if node.await_token.is_some() {
+            self.found = true;
+        }
+        node.visit_children_with(self);
+    }
+
+    // `await` within a function belongs to that function
+    fn visit_function(&mut self, _node: &Function, _parent: &dyn Node) {}
+
+    fn visit_arrow_expr(&mut self, _node: &ArrowExpr, _parent: &dyn Node) {}
+
+    fn visit_constructor(&mut self, _node: &Constructor, _parent: &dyn Node) {}
+
+    fn visit_getter_prop(&mut self, _node: &GetterProp, _parent: &dyn Node) {}
+
+    fn visit_setter_prop(&mut self, _node: &SetterProp, _parent: &dyn Node) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use swc_common::sync::Lrc;
+    use swc_common::SourceMap;
+
+    fn parse_module(code: &str) -> Module {
+        let source_map = Lrc::new(SourceMap::default());
+        parse(code, "test.js", &source_map).unwrap().0
+    }
+
+    #[test]
+    fn finds_await_outside_of_functions() {
+        assert!(has_top_level_await(&parse_module(
+            "const data = await fetch('/data');"
+        )));
+        assert!(has_top_level_await(&parse_module(
+            "if (ready) { console.log(await load()); }"
+        )));
+        assert!(has_top_level_await(&parse_module(
+            "for await (const line of lines) {}"
+        )));
+    }
+
+    #[test]
+    fn ignores_await_within_functions() {
+        assert!(!has_top_level_await(&parse_module(
+            "async function load() { await fetch('/data'); }\n\
+             const load2 = async () => { for await (const line of lines) {} };\n\
+             class Loader { async load() { await fetch('/data'); } }\n\
+             const loader = { async load() { await fetch('/data'); } };"
+        )));
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 pub fn create_module_id(path: &Path, project_root: &Path) -> String {
     String::from(
@@ -10,6 +10,57 @@ pub fn create_module_id(path: &Path, project_root: &Path) -> String {
     )
 }
 
+/// Returns the path of `to` relative to the directory `from` with `/`
+/// separators, e.g. `../src/index.js`
+pub fn relative_path(from: &Path, to: &Path) -> String {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec![String::from(".."); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|component| component.as_os_str().to_string_lossy().to_string()),
+    );
+    parts.join("/")
+}
+
+/// Removes `.` and `..` components without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Module ids keep the request query so `./file?raw` and `./file` are separate modules
+pub fn create_module_id_with_query(
+    path: &Path,
+    project_root: &Path,
+    query: Option<&str>,
+) -> String {
+    match query {
+        Some(query) => format!("{}?{}", create_module_id(path, project_root), query),
+        None => create_module_id(path, project_root),
+    }
+}
+
+/// Splits a request like `./shader.glsl?raw` into its path and query
+pub fn split_query(request: &str) -> (&str, Option<&str>) {
+    match request.find('?') {
+        Some(index) => (&request[..index], Some(&request[index + 1..])),
+        None => (request, None),
+    }
+}
+
 pub fn strip_invalid_chars(value: &str) -> String {
     value
         .chars()
@@ -21,3 +72,129 @@ pub fn strip_invalid_chars(value: &str) -> String {
         })
         .collect()
 }
+
+/// Stable FNV-1a hash rendered as 8 hex chars, used wherever generated names
+/// need to be identical between builds.
+pub fn short_hash(value: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in value {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)[..8].to_string()
+}
+
+pub fn to_js_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{2028}' => escaped.push_str("\\u2028"),
+            '\u{2029}' => escaped.push_str("\\u2029"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Words that can't be used as a binding name, e.g. `var class`
+const RESERVED_WORDS: &[&str] = &[
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Whether `value` is an identifier name, which is valid as a property or
+/// export name even if it is a reserved word
+pub fn is_identifier_name(value: &str) -> bool {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(c) if c == '$' || c == '_' || c.is_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c == '$' || c == '_' || c.is_alphanumeric())
+}
+
+/// Whether `value` can be declared as a binding, e.g. `var value`
+pub fn is_identifier(value: &str) -> bool {
+    is_identifier_name(value) && !RESERVED_WORDS.contains(&value)
+}
+
+/// Whether `value` is an identifier or a dotted path to a property of one,
+/// like `MyLib` or `MyCompany.widgets`
+pub fn is_global_name(value: &str) -> bool {
+    let mut segments = value.split('.');
+    segments.next().is_some_and(is_identifier) && segments.all(is_identifier_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_identifiers_and_dotted_paths_as_global_names() {
+        assert!(is_global_name("MyLib"));
+        assert!(is_global_name("$_lib2"));
+        assert!(is_global_name("MyCompany.widgets.default"));
+    }
+
+    #[test]
+    fn rejects_invalid_global_names() {
+        assert!(!is_global_name(""));
+        assert!(!is_global_name("my-lib"));
+        assert!(!is_global_name("2d"));
+        assert!(!is_global_name("class"));
+        assert!(!is_global_name("MyLib."));
+        assert!(!is_global_name("window[0]"));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::loaders::context::{find_files, is_context_query};
+use crate::loaders::glob::is_glob_query;
+use crate::Compilation;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// The files and directories of glob and context modules a compilation read
+pub struct WatchedPaths {
+    pub files: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+}
+
+pub fn get_watched_paths(c: &Compilation) -> WatchedPaths {
+    let mut paths = WatchedPaths {
+        files: vec![],
+        dirs: vec![],
+    };
+    for module in c.graph.modules.values() {
+        let is_dir = module
+            .id
+            .split_once('?')
+            .is_some_and(|(_, query)| is_glob_query(query) || is_context_query(query));
+
+        if is_dir {
+            paths.dirs.push(module.filepath.clone());
+        } else {
+            paths.files.push(module.filepath.clone());
+        }
+    }
+    paths
+}
+
+/// The modification time of each watched file. Every file in a watched
+/// directory is included, so adding or removing one changes the snapshot and
+/// its glob or context module is expanded again.
+pub type Snapshot = BTreeMap<PathBuf, Option<SystemTime>>;
+
+pub fn take_snapshot(paths: &WatchedPaths) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for filepath in &paths.files {
+        add_file(&mut snapshot, filepath.clone());
+    }
+    for dir in &paths.dirs {
+        for relative_path in find_files(dir).unwrap_or_default() {
+            add_file(&mut snapshot, dir.join(relative_path));
+        }
+    }
+    snapshot
+}
+
+fn add_file(snapshot: &mut Snapshot, filepath: PathBuf) {
+    let modified = fs::metadata(&filepath)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    snapshot.insert(filepath, modified);
+}